@@ -14,58 +14,211 @@
   What is the total number of distinct ways you can arrange the adapters to connect the carging outlet to your device?
 */
 
-pub fn jolt_diff_using_all_adapters(adapters: &Vec<i64>) -> i64 {
-  let mut jolt_diff1 = 0;
-  let mut jolt_diff3 = 0;
-  let mut current_jolt_val = 0;
-  for adapter in adapters {
-    let diff = adapter - current_jolt_val;
-    if diff < 0 || diff > 3 {
-      panic!("Adapter {} could not be applied to current joltage {}", adapter, diff);
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+
+pub fn jolt_diff_using_all_adapters(adapters: &[i64]) -> Result<i64, InvalidAdapters> {
+  let chain = validate_adapters(adapters)?;
+  let histogram = jolt_histogram(&chain);
+  let count = |difference| *histogram.get(&difference).unwrap_or(&0) as i64;
+  Ok(count(1) * count(3))
+}
+
+/// Everything wrong with a set of adapters that have to be used all together
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidAdapters {
+  /// Ratings that appear more than once
+  pub duplicates: Vec<i64>,
+  /// Neighboring ratings (lower, higher) that are more than 3 jolts apart
+  pub gaps: Vec<(i64, i64)>,
+}
+
+impl fmt::Display for InvalidAdapters {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut problems = self.duplicates.iter()
+      .map(|rating| format!("duplicate adapter {}", rating))
+      .collect::<Vec<String>>();
+    problems.extend(self.gaps.iter().map(|(lower, higher)| format!("cannot connect {} to {}", lower, higher)));
+    write!(f, "{}", problems.join(", "))
+  }
+}
+
+/// Sort the adapters into the chain that uses all of them, starting with the outlet at 0 jolts.
+/// Fails if any adapter is duplicated or the chain has a gap bigger than 3 jolts.
+pub fn validate_adapters(adapters: &[i64]) -> Result<Vec<i64>, InvalidAdapters> {
+  let mut chain = adapters.to_vec();
+  if !chain.contains(&0) {
+    chain.push(0);
+  }
+  chain.sort_unstable();
+  let mut duplicates = Vec::new();
+  let mut gaps = Vec::new();
+  for pair in chain.windows(2) {
+    let difference = pair[1] - pair[0];
+    if difference == 0 && duplicates.last() != Some(&pair[0]) {
+      duplicates.push(pair[0]);
+    } else if difference > 3 {
+      gaps.push((pair[0], pair[1]));
     }
-    if diff == 1 {
-      jolt_diff1 += 1;
-    } else if diff == 3 {
-      jolt_diff3 += 1
+  }
+  if duplicates.is_empty() && gaps.is_empty() {
+    Ok(chain)
+  } else {
+    Err(InvalidAdapters { duplicates, gaps })
+  }
+}
+
+/// None if there are too many configurations to fit in an i64; count_arrangements has the full number
+pub fn total_configurations(adapters: &[i64]) -> Option<i64> {
+  count_arrangements(adapters, &[1, 2, 3]).to_i64()
+}
+
+/// Count the ways to chain adapters from the charging outlet (0 jolts) up to the highest rated adapter,
+/// where each adapter in the chain must be rated higher than the one before it by one of the allowed differences.
+/// Adapters can be in any order. Adapters with the same rating are different choices, so a chain through
+/// a rating can use any one of them.
+pub fn count_arrangements(adapters: &[i64], allowed_differences: &[i64]) -> BigUint {
+  let ratings = sorted_ratings(adapters);
+  let mut copies: HashMap<i64, u32> = HashMap::new();
+  for &rating in adapters.iter().filter(|&&rating| rating > 0) {
+    *copies.entry(rating).or_insert(0) += 1;
+  }
+
+  // ways[rating] is the number of chains from the outlet that end at that rating
+  let mut ways: HashMap<i64, BigUint> = HashMap::new();
+  ways.insert(0, BigUint::from(1u32));
+  for &rating in &ratings[1..] {
+    let mut total = BigUint::from(0u32);
+    for &difference in allowed_differences.iter().filter(|&&difference| difference > 0) {
+      if let Some(count) = ways.get(&(rating - difference)) {
+        total += count;
+      }
     }
-    current_jolt_val = *adapter;
+    ways.insert(rating, total * copies[&rating]);
   }
-  return jolt_diff1 * jolt_diff3;
+  ways.remove(ratings.last().unwrap()).unwrap()
 }
 
-pub fn total_configurations(adapters: &Vec<i64>) -> i64 {
-  // slice array wherever there is a gap of 3
-  let mut slice_index_start = 0;
-  let mut current_jolt_val = 0;
-  let mut slices: Vec<&[i64]> = Vec::new();
-  for i in 0..adapters.len() {
-    let diff = adapters[i] - current_jolt_val;
-    if diff == 3 {
-      slices.push(&adapters[slice_index_start..i]);
-      slice_index_start = i;
+/// The outlet (0 jolts) followed by every distinct adapter rating in order
+fn sorted_ratings(adapters: &[i64]) -> Vec<i64> {
+  let mut ratings = adapters.iter().copied().filter(|&rating| rating > 0).collect::<Vec<i64>>();
+  ratings.push(0);
+  ratings.sort_unstable();
+  ratings.dedup();
+  ratings
+}
+
+/// Lazily lists every valid chain from the outlet to the highest rated adapter, the same chains count_arrangements counts.
+/// Each chain is the positions in adapters of the adapters it uses, from lowest to highest rating, so chains through
+/// two adapters with the same rating are listed separately. Use take to limit how many are generated.
+pub fn arrangements(adapters: &[i64], allowed_differences: &[i64]) -> Arrangements {
+  let ratings = sorted_ratings(adapters);
+  let target = *ratings.last().unwrap();
+  let mut differences = allowed_differences.iter().copied().filter(|&difference| difference > 0).collect::<Vec<i64>>();
+  differences.sort_unstable();
+  let mut positions: HashMap<i64, Vec<usize>> = HashMap::new();
+  for (position, &rating) in adapters.iter().enumerate().filter(|&(_, &rating)| rating > 0) {
+    positions.entry(rating).or_default().push(position);
+  }
+
+  // only step to adapters that can still reach the target, so the search never hits a dead end
+  let mut successors: HashMap<i64, Vec<(i64, usize)>> = HashMap::new();
+  successors.insert(target, Vec::new());
+  for &rating in ratings.iter().rev().skip(1) {
+    let next = differences.iter()
+      .map(|difference| rating + difference)
+      .filter(|next| successors.contains_key(next))
+      .flat_map(|next| positions[&next].iter().map(move |&position| (next, position)))
+      .collect::<Vec<(i64, usize)>>();
+    if !next.is_empty() {
+      successors.insert(rating, next);
     }
-    current_jolt_val = adapters[i];
   }
+  let stack = if successors.contains_key(&0) { vec![(0, None, 0)] } else { Vec::new() };
+  Arrangements { successors, target, stack }
+}
 
-  // separately compute total combinations for each slice
-  // multiply them together
-  return slices.iter()
-    .map(|slice| get_all_combos(slice))
-    .product();
+/// Depth first search over adapter chains. See arrangements.
+pub struct Arrangements {
+  successors: HashMap<i64, Vec<(i64, usize)>>,
+  target: i64,
+  // each adapter in the current chain (the outlet has no position) with the index of the next successor to try
+  stack: Vec<(i64, Option<usize>, usize)>,
 }
 
-fn get_all_combos(adapter_slice: &[i64]) -> i64 {
-  if adapter_slice.len() <= 2 {
-    return 1;
+impl Iterator for Arrangements {
+  type Item = Vec<usize>;
+
+  fn next(&mut self) -> Option<Vec<usize>> {
+    while let Some((rating, _, next_index)) = self.stack.last_mut() {
+      if *rating == self.target {
+        let chain = self.stack.iter().filter_map(|&(_, position, _)| position).collect();
+        self.stack.pop();
+        return Some(chain);
+      }
+      match self.successors[rating].get(*next_index) {
+        Some(&(next, position)) => {
+          *next_index += 1;
+          self.stack.push((next, Some(position), 0));
+        },
+        None => {
+          self.stack.pop();
+        },
+      }
+    }
+    None
   }
-  let mut j = 1;
-  let first = adapter_slice[0];
-  let mut combo_list = Vec::new();
-  while j < adapter_slice.len() && adapter_slice[j] - first <= 3 {
-    combo_list.push(get_all_combos(&adapter_slice[j..]));
-    j += 1;
+}
+
+/// The valid chain from the outlet to the highest rated adapter that uses the fewest adapters
+pub fn shortest_chain(adapters: &[i64], allowed_differences: &[i64]) -> Option<Vec<i64>> {
+  best_chain(adapters, allowed_differences, |length, best| length < best)
+}
+
+/// The valid chain from the outlet to the highest rated adapter that uses the most adapters
+pub fn longest_chain(adapters: &[i64], allowed_differences: &[i64]) -> Option<Vec<i64>> {
+  best_chain(adapters, allowed_differences, |length, best| length > best)
+}
+
+fn best_chain<F: Fn(usize, usize) -> bool>(adapters: &[i64], allowed_differences: &[i64], is_better: F) -> Option<Vec<i64>> {
+  let ratings = sorted_ratings(adapters);
+  // best[rating] is the length of the best chain ending at that rating, and the rating before it
+  let mut best: HashMap<i64, (usize, i64)> = HashMap::new();
+  best.insert(0, (1, 0));
+  for &rating in &ratings[1..] {
+    let mut best_here: Option<(usize, i64)> = None;
+    for &difference in allowed_differences.iter().filter(|&&difference| difference > 0) {
+      if let Some(&(length, _)) = best.get(&(rating - difference)) {
+        if best_here.map(|(best_length, _)| is_better(length + 1, best_length)).unwrap_or(true) {
+          best_here = Some((length + 1, rating - difference));
+        }
+      }
+    }
+    if let Some(best_here) = best_here {
+      best.insert(rating, best_here);
+    }
+  }
+
+  let mut rating = *ratings.last().unwrap();
+  best.get(&rating)?;
+  let mut chain = vec![rating];
+  while rating != 0 {
+    rating = best[&rating].1;
+    chain.push(rating);
+  }
+  chain.reverse();
+  Some(chain)
+}
+
+/// How many times each jolt difference appears between neighboring adapters in a chain
+pub fn jolt_histogram(chain: &[i64]) -> BTreeMap<i64, usize> {
+  let mut histogram = BTreeMap::new();
+  for pair in chain.windows(2) {
+    *histogram.entry(pair[1] - pair[0]).or_insert(0) += 1;
   }
-  return combo_list.iter().sum();
+  histogram
 }
 
 pub fn read_adapters() -> Vec<i64> {
@@ -181,7 +334,17 @@ mod tests {
     test_vec.push(0);
     test_vec.sort();
     test_vec.push(test_vec[test_vec.len() - 1] + 3);
-    assert_eq!(220, jolt_diff_using_all_adapters(&test_vec));
+    assert_eq!(Ok(220), jolt_diff_using_all_adapters(&test_vec));
+  }
+
+  #[test]
+  fn validate_unsorted_adapters() {
+    assert_eq!(Ok(vec![0, 1, 4, 5, 7]), validate_adapters(&[7, 4, 1, 5]));
+    let invalid = InvalidAdapters { duplicates: vec![4], gaps: vec![(4, 9), (9, 13)] };
+    assert_eq!(Err(invalid.clone()), validate_adapters(&[13, 4, 1, 4, 9, 4]));
+    assert_eq!(Err(invalid), jolt_diff_using_all_adapters(&[13, 4, 1, 4, 9, 4]));
+    assert_eq!(Err(InvalidAdapters { duplicates: vec![], gaps: vec![(0, 5)] }), validate_adapters(&[5, 6]));
+    assert_eq!("duplicate adapter 4, cannot connect 4 to 9", InvalidAdapters { duplicates: vec![4], gaps: vec![(4, 9)] }.to_string());
   }
 
   #[test]
@@ -191,7 +354,64 @@ mod tests {
     test_vec.sort();
     test_vec.push(test_vec[test_vec.len() - 1] + 3);
     let test_vec2 = vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22];
-    assert_eq!(8, total_configurations(&test_vec2));
-    assert_eq!(19208, total_configurations(&test_vec));
+    assert_eq!(Some(8), total_configurations(&test_vec2));
+    assert_eq!(Some(19208), total_configurations(&test_vec));
+    assert_eq!(None, total_configurations(&(1..=200).collect::<Vec<i64>>()));
+  }
+
+  #[test]
+  fn count_unsorted_with_other_differences() {
+    let unsorted = vec![28, 33, 18, 42, 31, 14, 46, 20, 48, 47, 24, 23, 49, 45, 19, 38, 39, 11, 1, 32, 25, 35, 8, 17, 7, 9, 4, 2, 34, 10, 3];
+    assert_eq!(BigUint::from(19208u32), count_arrangements(&unsorted, &[1, 2, 3]));
+    // either of the adapters rated 3 can be used
+    assert_eq!(BigUint::from(2u32), count_arrangements(&[5, 1, 4, 3, 2, 3], &[1]));
+    assert_eq!(BigUint::from(4u32), count_arrangements(&[1, 1, 2, 2], &[1]));
+    // 0 -> 4 -> 5, 0 -> 1 -> 5, or 0 -> 1 -> 2 -> 3 -> 4 -> 5
+    assert_eq!(BigUint::from(3u32), count_arrangements(&[5, 3, 2, 4, 1], &[1, 4]));
+    assert_eq!(BigUint::from(0u32), count_arrangements(&[1, 2, 7], &[1, 2, 3]));
+  }
+
+  #[test]
+  fn list_arrangements() {
+    let adapters = vec![16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4];
+    let ratings = |chain: &Vec<usize>| chain.iter().map(|&position| adapters[position]).collect::<Vec<i64>>();
+    let all = arrangements(&adapters, &[1, 2, 3]).collect::<Vec<Vec<usize>>>();
+    assert_eq!(8, all.len());
+    assert_eq!(vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19], ratings(&all[0]));
+    assert_eq!(vec![1, 4, 7, 10, 12, 15, 16, 19], ratings(&all[7]));
+    assert_eq!(2, arrangements(&adapters, &[1, 2, 3]).take(2).count());
+    assert_eq!(0, arrangements(&[1, 2, 7], &[1, 2, 3]).count());
+    assert_eq!(vec![Vec::<usize>::new()], arrangements(&[], &[1]).collect::<Vec<Vec<usize>>>());
+
+    // each adapter with a repeated rating is its own choice, as in count_arrangements
+    let repeated = arrangements(&[1, 1, 2, 2], &[1]).collect::<Vec<Vec<usize>>>();
+    assert_eq!(vec![vec![0, 2], vec![0, 3], vec![1, 2], vec![1, 3]], repeated);
+    for (adapters, allowed) in [(&[1, 1, 2, 2][..], &[1][..]), (&[5, 1, 4, 3, 2, 3], &[1]), (&adapters, &[1, 2, 3])] {
+      assert_eq!(count_arrangements(adapters, allowed), BigUint::from(arrangements(adapters, allowed).count()));
+    }
+  }
+
+  #[test]
+  fn shortest_and_longest() {
+    let adapters = vec![16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4];
+    let shortest = shortest_chain(&adapters, &[1, 2, 3]).unwrap();
+    assert_eq!(vec![0, 1, 4, 7, 10, 12, 15, 16, 19], shortest);
+    let longest = longest_chain(&adapters, &[1, 2, 3]).unwrap();
+    assert_eq!(vec![0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19], longest);
+    assert_eq!(None, shortest_chain(&[1, 2, 7], &[1, 2, 3]));
+
+    let histogram = jolt_histogram(&longest);
+    assert_eq!(Some(&7), histogram.get(&1));
+    assert_eq!(Some(&4), histogram.get(&3));
+    assert_eq!(None, histogram.get(&2));
+  }
+
+  #[test]
+  fn count_past_i64() {
+    // every step of 1, 2 or 3 is allowed, so the counts follow the tribonacci numbers
+    let adapters = (1..=200).collect::<Vec<i64>>();
+    let count = count_arrangements(&adapters, &[1, 2, 3]);
+    assert!(count.to_i64().is_none());
+    assert_eq!("52622583840983769603765180599790256716084480555530641", count.to_string());
   }
 }
\ No newline at end of file
@@ -29,7 +29,10 @@
   Given the new visibility method and the rule change for occupied seats becoming empty, once equilibrium is reached, how many seats end up occupied?
 */
 
-use std::cmp;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Seat {
@@ -38,162 +41,436 @@ pub enum Seat {
   Occupied,
 }
 
-pub fn count_stable_occupied(seats: &Vec<Vec<Seat>>) -> usize {
-  let mut current_setup = seats.clone();
-  let mut next_setup = seat_round(&current_setup);
-  while current_setup != next_setup {
-    current_setup = next_setup;
-    next_setup = seat_round(&current_setup);
-  }
-  return next_setup.iter().flat_map(|row| row)
-    .filter(|&seat| *seat == Seat::Occupied)
-    .count();
+pub fn count_stable_occupied(seats: &[Vec<Seat>]) -> usize {
+  count_stable(seats, &SeatingPolicy::adjacent(), usize::MAX).expect("the part 1 rules always settle")
+}
+
+pub fn count_stable_los(seats: &[Vec<Seat>]) -> usize {
+  count_stable(seats, &SeatingPolicy::line_of_sight(), usize::MAX).expect("the part 2 rules always settle")
+}
+
+/// A round limit for the other seating policies, which might never settle
+pub const MAX_ROUNDS: usize = 10_000;
+
+/// Returns the (row, col) positions that count as neighbors of the seat at row and col
+pub type NeighborPositions = fn(usize, usize, &[Vec<Seat>]) -> Vec<(usize, usize)>;
+
+/// Which seats a person looks at when deciding whether to sit down or leave
+#[derive(Clone, Copy)]
+pub enum Neighborhood {
+  /// The 8 spaces immediately around the seat
+  Adjacent,
+  /// The first seat visible in each of the 8 directions
+  LineOfSight,
+  /// Every space within this many rows and columns of the seat
+  Radius(usize),
+  Custom(NeighborPositions),
 }
 
-fn seat_round(current_setup: &Vec<Vec<Seat>>) -> Vec<Vec<Seat>> {
-  let mut next_setup = Vec::new();
-  for row in 0..current_setup.len() {
-    let mut next_row = Vec::new();
-    for col in 0..current_setup[row].len() {
-      let adjacent_seats = get_adjacent_seats(row, col, &current_setup);
-      let occupied_adjacent: i32 = adjacent_seats.iter()
-        .map(|&seat| if *seat == Seat::Occupied {1} else {0})
-        .sum();
-      let next_seat = match current_setup[row][col] {
-        Seat::Floor => Seat::Floor,
-        Seat::Empty => if occupied_adjacent == 0 { Seat::Occupied } else { Seat::Empty },
-        Seat::Occupied => if occupied_adjacent >=4 { Seat::Empty } else { Seat::Occupied },
-      };
-      next_row.push(next_seat);
+impl Neighborhood {
+  /// The spaces the seat at row and col looks at. Adjacent and Radius include floor,
+  /// and LineOfSight gives floor for a direction with no seat in it.
+  pub fn neighbors<'a>(&self, row: usize, col: usize, seats: &'a [Vec<Seat>]) -> Vec<&'a Seat> {
+    match self {
+      Neighborhood::Adjacent => get_adjacent_seats(row, col, seats),
+      Neighborhood::LineOfSight => get_adjacent_seats_los(row, col, seats),
+      Neighborhood::Radius(radius) => radius_positions(row, col, *radius, seats).iter()
+        .map(|&(r, c)| &seats[r][c])
+        .collect(),
+      Neighborhood::Custom(positions) => positions(row, col, seats).iter()
+        .filter_map(|&(r, c)| seats.get(r)?.get(c))
+        .collect(),
     }
-    next_setup.push(next_row);
   }
-  return next_setup;
+
+  /// The (row, col) positions of the seats that count as neighbors, floor is never included
+  pub fn positions(&self, row: usize, col: usize, seats: &[Vec<Seat>]) -> Vec<(usize, usize)> {
+    let positions = match self {
+      Neighborhood::Adjacent => radius_positions(row, col, 1, seats),
+      Neighborhood::LineOfSight => line_of_sight_positions(row, col, seats),
+      Neighborhood::Radius(radius) => radius_positions(row, col, *radius, seats),
+      Neighborhood::Custom(positions) => positions(row, col, seats),
+    };
+    // a custom neighborhood can point past the edge of the seating area
+    positions.into_iter()
+      .filter(|&(r, c)| seats.get(r).and_then(|line| line.get(c)).map(|seat| *seat != Seat::Floor).unwrap_or(false))
+      .collect()
+  }
+}
+
+/// When people sit down or leave, based on how many of their neighbors are occupied
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeatRules {
+  /// An empty seat becomes occupied if no more than this many neighbors are occupied
+  pub sit_at_most: usize,
+  /// An occupied seat becomes empty if at least this many neighbors are occupied
+  pub leave_at_least: usize,
 }
 
-pub fn count_stable_los(seats: &Vec<Vec<Seat>>) -> usize {
-  let mut current_setup = seats.clone();
-  let mut next_setup = seat_rount_los(&current_setup);
-  while current_setup != next_setup {
-    current_setup = next_setup;
-    next_setup = seat_rount_los(&current_setup);
+#[derive(Clone, Copy)]
+pub struct SeatingPolicy {
+  pub neighborhood: Neighborhood,
+  pub rules: SeatRules,
+}
+
+impl SeatingPolicy {
+  /// Part 1: adjacent seats, and people leave when 4 are occupied
+  pub fn adjacent() -> SeatingPolicy {
+    SeatingPolicy { neighborhood: Neighborhood::Adjacent, rules: SeatRules { sit_at_most: 0, leave_at_least: 4 } }
+  }
+
+  /// Part 2: visible seats, and people leave when 5 are occupied
+  pub fn line_of_sight() -> SeatingPolicy {
+    SeatingPolicy { neighborhood: Neighborhood::LineOfSight, rules: SeatRules { sit_at_most: 0, leave_at_least: 5 } }
   }
-  return next_setup.iter().flat_map(|row| row)
+}
+
+/// Run rounds until nothing changes, then count the occupied seats.
+/// None if the seating goes round in a cycle or doesn't settle within `max_rounds`.
+pub fn count_stable(seats: &[Vec<Seat>], policy: &SeatingPolicy, max_rounds: usize) -> Option<usize> {
+  let graph = SeatGraph::build(seats, &policy.neighborhood);
+  let mut simulation = SeatSimulation::new(&graph, policy.rules);
+  match simulation.run(max_rounds) {
+    SeatingOutcome::Stable { .. } => Some(simulation.occupied()),
+    _ => None,
+  }
+}
+
+pub fn count_occupied(seats: &[Vec<Seat>]) -> usize {
+  seats.iter().flatten()
     .filter(|&seat| *seat == Seat::Occupied)
-    .count();
+    .count()
+}
+
+/// A single round over the whole seating area, looking at each seat's neighbors directly.
+/// Fine for one round, but `SeatSimulation` is much faster at running many.
+pub fn seat_round(current_setup: &[Vec<Seat>], policy: &SeatingPolicy) -> Vec<Vec<Seat>> {
+  current_setup.iter().enumerate()
+    .map(|(row, line)| line.iter().enumerate()
+      .map(|(col, seat)| {
+        let occupied_adjacent = policy.neighborhood.neighbors(row, col, current_setup).iter()
+          .filter(|&&neighbor| *neighbor == Seat::Occupied)
+          .count();
+        match seat {
+          Seat::Floor => Seat::Floor,
+          Seat::Empty => if occupied_adjacent <= policy.rules.sit_at_most { Seat::Occupied } else { Seat::Empty },
+          Seat::Occupied => if occupied_adjacent >= policy.rules.leave_at_least { Seat::Empty } else { Seat::Occupied },
+        }
+      })
+      .collect())
+    .collect()
 }
 
-fn seat_rount_los(current_setup: &Vec<Vec<Seat>>) -> Vec<Vec<Seat>> {
-  let mut next_setup = Vec::new();
-  for row in 0..current_setup.len() {
-    let mut next_row = Vec::new();
-    for col in 0..current_setup[row].len() {
-      let adjacent_seats = get_adjacent_seats_los(row, col, &current_setup);
-      let occupied_adjacent: i32 = adjacent_seats.iter()
-        .map(|&seat| if *seat == Seat::Occupied {1} else {0})
-        .sum();
-      let next_seat = match current_setup[row][col] {
-        Seat::Floor => Seat::Floor,
-        Seat::Empty => if occupied_adjacent == 0 { Seat::Occupied } else { Seat::Empty },
-        Seat::Occupied => if occupied_adjacent >=5 { Seat::Empty } else { Seat::Occupied },
-      };
-      next_row.push(next_seat);
+/// How a seating simulation ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SeatingOutcome {
+  /// Nothing changes after this many rounds
+  Stable { rounds: usize },
+  /// The seating after round `start` comes back every `period` rounds, forever
+  Cycle { period: usize, start: usize },
+  /// Still changing when the round limit was hit
+  LimitReached { rounds: usize },
+}
+
+impl fmt::Display for SeatingOutcome {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      SeatingOutcome::Stable { rounds } => write!(f, "stable after {} rounds", rounds),
+      SeatingOutcome::Cycle { period, start } => write!(f, "repeats every {} rounds from round {}", period, start),
+      SeatingOutcome::LimitReached { rounds } => write!(f, "still changing after {} rounds", rounds),
     }
-    next_setup.push(next_row);
   }
-  return next_setup;
 }
 
-// My first use of lifetimes!
-fn get_adjacent_seats<'a>(row: usize, col: usize, seats: &'a Vec<Vec<Seat>>) -> Vec<&'a Seat> {
-  let mut adjacent_seats = Vec::new();
-  // To for loops with uzise bounds (can't be less than 0) are a problem
-  for r in cmp::max(0, row as i32 - 1) as usize..=(row + 1) {
-    for c in cmp::max(0, col as i32 - 1 ) as usize..=(col + 1) {
-      if !(r == row && c == col) && r < seats.len() && c < seats[r].len() {
-        adjacent_seats.push(&seats[r][c]);
+/// Every seat and the seats it looks at, worked out once up front.
+/// Floor never changes so it isn't part of the graph, and walking the rays for line of sight
+/// only has to happen here rather than every round.
+pub struct SeatGraph {
+  layout: Vec<Vec<Seat>>,
+  positions: Vec<(usize, usize)>,
+  /// Seats each seat looks at
+  neighbors: Vec<Vec<usize>>,
+  /// Seats that look at each seat, the ones to re-check when it changes
+  watchers: Vec<Vec<usize>>,
+}
+
+impl SeatGraph {
+  pub fn build(seats: &[Vec<Seat>], neighborhood: &Neighborhood) -> SeatGraph {
+    let mut index = HashMap::new();
+    let mut positions = Vec::new();
+    for (row, line) in seats.iter().enumerate() {
+      for (col, seat) in line.iter().enumerate() {
+        if *seat != Seat::Floor {
+          index.insert((row, col), positions.len());
+          positions.push((row, col));
+        }
+      }
+    }
+
+    let neighbors: Vec<Vec<usize>> = positions.iter()
+      .map(|&(row, col)| neighborhood.positions(row, col, seats).iter()
+        .map(|position| index[position])
+        .collect())
+      .collect();
+    let mut watchers = vec![Vec::new(); positions.len()];
+    for (seat, seen) in neighbors.iter().enumerate() {
+      for &neighbor in seen {
+        watchers[neighbor].push(seat);
       }
     }
+    SeatGraph { layout: seats.to_vec(), positions, neighbors, watchers }
   }
-  return adjacent_seats;
+
+  pub fn seat_count(&self) -> usize {
+    self.positions.len()
+  }
+}
+
+/// Runs the seating rules over a `SeatGraph`.
+/// Each round reads from one buffer and writes the other, and only the seats next to
+/// something that changed in the previous round get looked at again.
+pub struct SeatSimulation<'a> {
+  graph: &'a SeatGraph,
+  rules: SeatRules,
+  occupied: Vec<bool>,
+  next: Vec<bool>,
+  to_check: Vec<usize>,
+  queued: Vec<bool>,
+  rounds: usize,
 }
 
-fn get_adjacent_seats_los<'a>(row: usize, col: usize, seats: &'a Vec<Vec<Seat>>) -> Vec<&'a Seat> {
-  let mut adjacent_seats = Vec::new();
-  
-  let mut diag_up_left_row = row;
-  let mut diag_up_left_col = col;
-  let mut diag_up_left_seat = &Seat::Floor;
-  while diag_up_left_col > 0 && diag_up_left_row > 0 && *diag_up_left_seat == Seat::Floor {
-    diag_up_left_col -= 1;
-    diag_up_left_row -= 1;
-    diag_up_left_seat = &seats[diag_up_left_row][diag_up_left_col];
+impl<'a> SeatSimulation<'a> {
+  /// Starts from the seats as they were when the graph was built
+  pub fn new(graph: &'a SeatGraph, rules: SeatRules) -> SeatSimulation<'a> {
+    let occupied: Vec<bool> = graph.positions.iter()
+      .map(|&(row, col)| graph.layout[row][col] == Seat::Occupied)
+      .collect();
+    SeatSimulation::from_state(graph, rules, occupied, 0)
   }
-  adjacent_seats.push(diag_up_left_seat);
 
-  let mut left_col = col;
-  let mut left_seat = &Seat::Floor;
-  while left_col > 0 && *left_seat == Seat::Floor {
-    left_col -= 1;
-    left_seat = &seats[row][left_col];
+  // checking every seat in the first round gives the same result no matter how the state was reached
+  fn from_state(graph: &'a SeatGraph, rules: SeatRules, occupied: Vec<bool>, rounds: usize) -> SeatSimulation<'a> {
+    SeatSimulation {
+      graph,
+      rules,
+      next: occupied.clone(),
+      occupied,
+      to_check: (0..graph.seat_count()).collect(),
+      queued: vec![true; graph.seat_count()],
+      rounds,
+    }
   }
-  adjacent_seats.push(left_seat);
-
-  let mut diag_down_left_row = row;
-  let mut diag_down_left_col = col;
-  let mut diag_down_left_seat = &Seat::Floor;
-  while diag_down_left_col > 0 && diag_down_left_row < seats.len() - 1 && *diag_down_left_seat == Seat::Floor {
-    diag_down_left_col -= 1;
-    diag_down_left_row += 1;
-    diag_down_left_seat = &seats[diag_down_left_row][diag_down_left_col];
+
+  /// Run a single round, returns how many seats changed
+  pub fn step(&mut self) -> usize {
+    let mut changed = Vec::new();
+    let to_check = std::mem::take(&mut self.to_check);
+    for seat in to_check {
+      self.queued[seat] = false;
+      let next = self.next_state(seat);
+      self.next[seat] = next;
+      if next != self.occupied[seat] {
+        changed.push(seat);
+      }
+    }
+    std::mem::swap(&mut self.occupied, &mut self.next);
+    // bring the old buffer back in line so it can be written to next round
+    for &seat in &changed {
+      self.next[seat] = self.occupied[seat];
+    }
+
+    for &seat in &changed {
+      for &affected in self.graph.watchers[seat].iter().chain(std::iter::once(&seat)) {
+        if !self.queued[affected] {
+          self.queued[affected] = true;
+          self.to_check.push(affected);
+        }
+      }
+    }
+    if !changed.is_empty() {
+      self.rounds += 1;
+    }
+    changed.len()
   }
-  adjacent_seats.push(diag_down_left_seat);
 
-  let mut down_row = row;
-  let mut down_seat = &Seat::Floor;
-  while down_row < seats.len() - 1 && *down_seat == Seat::Floor {
-    down_row += 1;
-    down_seat = &seats[down_row][col];
+  /// Whether the seat is occupied after the next round
+  fn next_state(&self, seat: usize) -> bool {
+    let occupied_neighbors = self.graph.neighbors[seat].iter()
+      .filter(|&&neighbor| self.occupied[neighbor])
+      .count();
+    if self.occupied[seat] {
+      occupied_neighbors < self.rules.leave_at_least
+    } else {
+      occupied_neighbors <= self.rules.sit_at_most
+    }
   }
-  adjacent_seats.push(down_seat);
-
-  let mut diag_down_right_row = row;
-  let mut diag_down_right_col = col;
-  let mut diag_down_right_seat = &Seat::Floor;
-  while diag_down_right_row < seats.len() - 1 && diag_down_right_col < seats[row].len() - 1 && *diag_down_right_seat == Seat::Floor {
-    diag_down_right_row += 1;
-    diag_down_right_col += 1;
-    diag_down_right_seat = &seats[diag_down_right_row][diag_down_right_col];
+
+  /// Step until a round changes nothing, the seating repeats an earlier round, or `max_rounds` rounds have run
+  pub fn run(&mut self, max_rounds: usize) -> SeatingOutcome {
+    self.run_with(max_rounds, |_| {})
   }
-  adjacent_seats.push(diag_down_right_seat);
 
-  let mut right_col = col;
-  let mut right_seat = &Seat::Floor;
-  while right_col < seats[row].len() -1 && *right_seat == Seat::Floor {
-    right_col += 1;
-    right_seat = &seats[row][right_col];
+  /// Like `run`, calling `on_round` after every round that changed something.
+  /// Only a hash of each round is kept. When a hash comes up again, the earlier round is
+  /// replayed from the starting state to make sure the seating really repeats.
+  pub fn run_with<F: FnMut(&SeatSimulation)>(&mut self, max_rounds: usize, mut on_round: F) -> SeatingOutcome {
+    let (first_state, first_round) = (self.occupied.clone(), self.rounds);
+    let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+    seen.entry(state_hash(&self.occupied)).or_default().push(self.rounds);
+    loop {
+      if self.rounds >= max_rounds {
+        // look ahead without running another round, so seating that has just settled isn't reported as still changing
+        let settled = self.to_check.iter().all(|&seat| self.next_state(seat) == self.occupied[seat]);
+        return if settled {
+          SeatingOutcome::Stable { rounds: self.rounds }
+        } else {
+          SeatingOutcome::LimitReached { rounds: self.rounds }
+        };
+      }
+      if self.step() == 0 {
+        return SeatingOutcome::Stable { rounds: self.rounds };
+      }
+      on_round(self);
+      let rounds = seen.entry(state_hash(&self.occupied)).or_default();
+      let repeat = rounds.iter().copied().find(|&start| {
+        let mut replay = SeatSimulation::from_state(self.graph, self.rules, first_state.clone(), first_round);
+        while replay.rounds < start {
+          replay.step();
+        }
+        replay.occupied == self.occupied
+      });
+      if let Some(start) = repeat {
+        return SeatingOutcome::Cycle { period: self.rounds - start, start };
+      }
+      rounds.push(self.rounds);
+    }
   }
-  adjacent_seats.push(right_seat);
-
-  let mut diag_up_right_row = row;
-  let mut diag_up_right_col = col;
-  let mut diag_up_right_seat = &Seat::Floor;
-  while diag_up_right_row > 0 && diag_up_right_col < seats[row].len() - 1 && *diag_up_right_seat == Seat::Floor {
-    diag_up_right_row -= 1;
-    diag_up_right_col += 1;
-    diag_up_right_seat = &seats[diag_up_right_row][diag_up_right_col];
+
+  pub fn occupied(&self) -> usize {
+    self.occupied.iter().filter(|&&occupied| occupied).count()
   }
-  adjacent_seats.push(diag_up_right_seat);
 
-  let mut up_row = row;
-  let mut up_seat = &Seat::Floor;
-  while up_row > 0 && *up_seat == Seat::Floor {
-    up_row -= 1;
-    up_seat = &seats[up_row][col];
+  /// The current state of the whole seating area, floor included
+  pub fn seats(&self) -> Vec<Vec<Seat>> {
+    let mut seats = self.graph.layout.clone();
+    for (seat, &(row, col)) in self.graph.positions.iter().enumerate() {
+      seats[row][col] = if self.occupied[seat] { Seat::Occupied } else { Seat::Empty };
+    }
+    seats
   }
-  adjacent_seats.push(up_seat);
+}
+
+fn state_hash(occupied: &[bool]) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  occupied.hash(&mut hasher);
+  hasher.finish()
+}
 
-  return adjacent_seats;
+/// The seating area after a round, round 0 being the starting layout
+pub struct Frame {
+  pub round: usize,
+  pub occupied: usize,
+  pub seats: Vec<Vec<Seat>>,
+}
+
+/// Every round from the starting layout up to the one where the simulation ended, and how it ended
+pub fn frames(seats: &[Vec<Seat>], policy: &SeatingPolicy, max_rounds: usize) -> (Vec<Frame>, SeatingOutcome) {
+  let graph = SeatGraph::build(seats, &policy.neighborhood);
+  let mut simulation = SeatSimulation::new(&graph, policy.rules);
+  let frame = |round: usize, seats: Vec<Vec<Seat>>| Frame { round, occupied: count_occupied(&seats), seats };
+  let mut frames = vec![frame(0, simulation.seats())];
+  let outcome = simulation.run_with(max_rounds, |simulation| {
+    let seats = simulation.seats();
+    // the graph should always agree with running a round seat by seat
+    debug_assert_eq!(seat_round(&frames.last().unwrap().seats, policy), seats);
+    frames.push(frame(frames.len(), seats));
+  });
+  (frames, outcome)
+}
+
+/// Draws the seats the same way as the puzzle input
+pub fn render_ascii(seats: &[Vec<Seat>]) -> String {
+  seats.iter()
+    .map(|row| row.iter()
+      .map(|seat| match seat {
+        Seat::Floor => '.',
+        Seat::Empty => 'L',
+        Seat::Occupied => '#',
+      })
+      .collect::<String>())
+    .collect::<Vec<String>>()
+    .join("\n")
+}
+
+/// Draws the seats as a plain text PPM image, each seat being a scale by scale square
+pub fn render_ppm(seats: &[Vec<Seat>], scale: usize) -> String {
+  let width = seats.iter().map(|row| row.len()).max().unwrap_or(0) * scale;
+  let mut image = format!("P3\n{} {}\n255\n", width, seats.len() * scale);
+  for row in seats {
+    let line = row.iter()
+      .map(|seat| match seat {
+        Seat::Floor => "40 40 40",
+        Seat::Empty => "60 180 75",
+        Seat::Occupied => "230 25 75",
+      })
+      .flat_map(|pixel| std::iter::repeat_n(pixel, scale))
+      .collect::<Vec<&str>>()
+      .join(" ");
+    for _ in 0..scale {
+      image.push_str(&line);
+      image.push('\n');
+    }
+  }
+  image
+}
+
+fn radius_positions(row: usize, col: usize, radius: usize, seats: &[Vec<Seat>]) -> Vec<(usize, usize)> {
+  let mut positions = Vec::new();
+  for r in row.saturating_sub(radius)..=(row + radius) {
+    for c in col.saturating_sub(radius)..=(col + radius) {
+      if !(r == row && c == col) && r < seats.len() && c < seats[r].len() {
+        positions.push((r, c));
+      }
+    }
+  }
+  positions
+}
+
+fn get_adjacent_seats(row: usize, col: usize, seats: &[Vec<Seat>]) -> Vec<&Seat> {
+  radius_positions(row, col, 1, seats).iter()
+    .map(|&(r, c)| &seats[r][c])
+    .collect()
+}
+
+/// Up left, left, down left, down, down right, right, up right, up
+const DIRECTIONS: [(i64, i64); 8] = [(-1, -1), (0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0)];
+
+/// The first seat in each direction, or floor if there isn't one
+fn get_adjacent_seats_los(row: usize, col: usize, seats: &[Vec<Seat>]) -> Vec<&Seat> {
+  DIRECTIONS.iter()
+    .map(|&direction| first_seat(row, col, direction, seats).map(|(r, c)| &seats[r][c]).unwrap_or(&Seat::Floor))
+    .collect()
+}
+
+/// The first seat in each direction, looking past floor
+fn line_of_sight_positions(row: usize, col: usize, seats: &[Vec<Seat>]) -> Vec<(usize, usize)> {
+  DIRECTIONS.iter()
+    .filter_map(|&direction| first_seat(row, col, direction, seats))
+    .collect()
+}
+
+fn first_seat(row: usize, col: usize, (row_step, col_step): (i64, i64), seats: &[Vec<Seat>]) -> Option<(usize, usize)> {
+  let (mut r, mut c) = (row as i64, col as i64);
+  loop {
+    r += row_step;
+    c += col_step;
+    if r < 0 || c < 0 || r as usize >= seats.len() || c as usize >= seats[r as usize].len() {
+      return None;
+    }
+    if seats[r as usize][c as usize] != Seat::Floor {
+      return Some((r as usize, c as usize));
+    }
+  }
 }
 
 pub fn read_input() -> String {
@@ -303,6 +580,7 @@ pub fn parse_seating(input: &str) -> Vec<Vec<Seat>> {
         row.push(Seat::Empty);
       } else if item == '.' {
         row.push(Seat::Floor);
+
       } else {
         panic!("unexpected input seat {}", item);
       }
@@ -316,6 +594,17 @@ pub fn parse_seating(input: &str) -> Vec<Vec<Seat>> {
 mod tests {
   use super::*;
 
+  const EXAMPLE: &str = "L.LL.LL.LL
+      LLLLLLL.LL
+      L.L.L..L..
+      LLLL.LL.LL
+      L.LL.LL.LL
+      L.LLLLL.LL
+      ..L.L.....
+      LLLLLLLLLL
+      L.LLLLLL.L
+      L.LLLLL.LL";
+
   #[test]
   fn vector_eqaul() {
     let vec1 = vec![Seat::Floor, Seat::Empty, Seat::Occupied];
@@ -370,6 +659,32 @@ mod tests {
     assert_eq!(vec![&Seat::Empty, &Seat::Floor, &Seat::Floor, &Seat::Occupied, &Seat::Floor, &Seat::Floor, &Seat::Floor, &Seat::Occupied], get_adjacent_seats_los(1, 2, &seats));
   }
 
+  #[test]
+  fn neighbor_positions() {
+    let seats = vec![
+        vec![Seat::Floor, Seat::Empty, Seat::Occupied],
+        vec![Seat::Floor, Seat::Empty, Seat::Empty],
+        vec![Seat::Floor, Seat::Floor, Seat::Occupied]];
+    // floor is never a neighbor
+    assert_eq!(vec![(0, 1), (0, 2), (1, 2), (2, 2)], Neighborhood::Adjacent.positions(1, 1, &seats));
+    assert_eq!(vec![(0, 1), (1, 1)], Neighborhood::Adjacent.positions(0, 0, &seats));
+    assert_eq!(vec![(0, 1), (0, 2), (1, 1), (2, 2)], Neighborhood::Adjacent.positions(1, 2, &seats));
+
+    let seats = vec![
+        vec![Seat::Floor, Seat::Empty, Seat::Occupied],
+        vec![Seat::Floor, Seat::Floor, Seat::Empty],
+        vec![Seat::Empty, Seat::Floor, Seat::Occupied]];
+    assert_eq!(vec![(2, 0), (2, 2), (0, 1)], Neighborhood::LineOfSight.positions(0, 0, &seats));
+    assert_eq!(vec![(0, 1), (2, 2), (0, 2)], Neighborhood::LineOfSight.positions(1, 2, &seats));
+
+    // the spaces below and to the right, even past the edge
+    let below_right = Neighborhood::Custom(|row, col, _| vec![(row + 1, col), (row, col + 1)]);
+    assert_eq!(vec![(1, 2)], below_right.positions(0, 2, &seats));
+    assert_eq!(vec![&Seat::Empty], below_right.neighbors(0, 2, &seats));
+    let graph = SeatGraph::build(&seats, &below_right);
+    assert_eq!(5, graph.seat_count());
+  }
+
   #[test]
   fn test_los_seating() {
     let input = 
@@ -387,4 +702,92 @@ mod tests {
     assert_eq!(26, count_stable_los(&seats));
   }
 
+  #[test]
+  fn seat_graph_rounds() {
+    let seats = parse_seating(EXAMPLE);
+    let graph = SeatGraph::build(&seats, &Neighborhood::LineOfSight);
+    assert_eq!(71, graph.seat_count());
+    let mut simulation = SeatSimulation::new(&graph, SeatingPolicy::line_of_sight().rules);
+    // everyone sits down, then everyone who can see 5 others gets up
+    assert_eq!(71, simulation.step());
+    simulation.step();
+    assert_eq!(7, simulation.occupied());
+    assert_eq!(SeatingOutcome::Stable { rounds: 6 }, simulation.run(MAX_ROUNDS));
+    assert_eq!(26, simulation.occupied());
+
+    // the part 1 rules settle after exactly 5 rounds, so a limit of 5 isn't hit
+    let graph = SeatGraph::build(&seats, &Neighborhood::Adjacent);
+    let mut simulation = SeatSimulation::new(&graph, SeatingPolicy::adjacent().rules);
+    assert_eq!(SeatingOutcome::LimitReached { rounds: 4 }, SeatSimulation::new(&graph, SeatingPolicy::adjacent().rules).run(4));
+    assert_eq!(SeatingOutcome::Stable { rounds: 5 }, simulation.run(5));
+    assert_eq!(0, simulation.step());
+    assert_eq!(37, simulation.occupied());
+  }
+
+  #[test]
+  fn single_rounds() {
+    let seats = parse_seating(EXAMPLE);
+    let first = seat_round(&seats, &SeatingPolicy::adjacent());
+    assert_eq!(71, count_occupied(&first));
+    let second = seat_round(&first, &SeatingPolicy::adjacent());
+    assert_eq!("#.LL.L#.##", render_ascii(&second).lines().next().unwrap());
+    assert_eq!(20, count_occupied(&second));
+  }
+
+  #[test]
+  fn seating_frames() {
+    let (frames, outcome) = frames(&parse_seating(EXAMPLE), &SeatingPolicy::adjacent(), MAX_ROUNDS);
+    assert_eq!(SeatingOutcome::Stable { rounds: 5 }, outcome);
+    assert_eq!(vec![0, 71, 20, 51, 30, 37], frames.iter().map(|frame| frame.occupied).collect::<Vec<usize>>());
+    assert_eq!(5, frames.last().unwrap().round);
+    assert_eq!(EXAMPLE.replace(' ', ""), render_ascii(&frames[0].seats));
+    assert_eq!("#.#L.L#.##", render_ascii(&frames[5].seats).lines().next().unwrap());
+
+    let ppm = render_ppm(&parse_seating("L.\n.L"), 2);
+    assert!(ppm.starts_with("P3\n4 4\n255\n60 180 75 60 180 75 40 40 40 40 40 40\n"));
+    assert_eq!(7, ppm.lines().count());
+  }
+
+  #[test]
+  fn test_other_policies() {
+    let seats = parse_seating(EXAMPLE);
+    let radius_one = SeatingPolicy { neighborhood: Neighborhood::Radius(1), rules: SeatRules { sit_at_most: 0, leave_at_least: 4 } };
+    assert_eq!(Some(37), count_stable(&seats, &radius_one, MAX_ROUNDS));
+    // only the seats to the left and right in the same row matter
+    let same_row = SeatingPolicy {
+      neighborhood: Neighborhood::Custom(|row, col, seats| {
+        let mut positions = vec![(row, col + 1)];
+        if col > 0 {
+          positions.push((row, col - 1));
+        }
+        positions.into_iter().filter(|&(r, c)| c < seats[r].len()).collect()
+      }),
+      rules: SeatRules { sit_at_most: 0, leave_at_least: 2 },
+    };
+    let graph = SeatGraph::build(&seats, &same_row.neighborhood);
+    let mut simulation = SeatSimulation::new(&graph, same_row.rules);
+    assert_eq!(71, simulation.step());
+    assert_eq!(71, simulation.occupied());
+    assert_eq!(Some(56), count_stable(&seats, &same_row, MAX_ROUNDS));
+  }
+
+  #[test]
+  fn seating_cycles() {
+    let seats = parse_seating(EXAMPLE);
+    // everyone always gets up and sits back down the next round
+    let restless = SeatingPolicy { neighborhood: Neighborhood::Adjacent, rules: SeatRules { sit_at_most: 8, leave_at_least: 0 } };
+    let graph = SeatGraph::build(&seats, &restless.neighborhood);
+    assert_eq!(SeatingOutcome::Cycle { period: 2, start: 0 }, SeatSimulation::new(&graph, restless.rules).run(MAX_ROUNDS));
+    assert_eq!(SeatingOutcome::LimitReached { rounds: 1 }, SeatSimulation::new(&graph, restless.rules).run(1));
+    assert_eq!(SeatingOutcome::LimitReached { rounds: 0 }, SeatSimulation::new(&graph, restless.rules).run(0));
+    // run only knows about the rounds since it was called
+    let mut simulation = SeatSimulation::new(&graph, restless.rules);
+    simulation.step();
+    simulation.step();
+    simulation.step();
+    assert_eq!(SeatingOutcome::Cycle { period: 2, start: 3 }, simulation.run(MAX_ROUNDS));
+    assert_eq!(None, count_stable(&seats, &restless, MAX_ROUNDS));
+
+  }
+
 }
\ No newline at end of file
@@ -28,9 +28,10 @@
   What is the Manhattan distance between that location and the ship's starting position?
 */
 
-const DIRECTION_ORDER: [Direction;4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Sub};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum NavInstruction {
   North(i32),
   East(i32),
@@ -49,6 +50,32 @@ pub enum Direction {
   West,
 }
 
+impl Direction {
+  /// One step in this direction
+  pub fn unit(&self) -> Vector {
+    match self {
+      Direction::North => Vector::new(0, 1),
+      Direction::East => Vector::new(1, 0),
+      Direction::South => Vector::new(0, -1),
+      Direction::West => Vector::new(-1, 0),
+    }
+  }
+}
+
+impl fmt::Display for NavInstruction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      NavInstruction::North(value) => write!(f, "N{}", value),
+      NavInstruction::East(value) => write!(f, "E{}", value),
+      NavInstruction::South(value) => write!(f, "S{}", value),
+      NavInstruction::West(value) => write!(f, "W{}", value),
+      NavInstruction::Forward(value) => write!(f, "F{}", value),
+      NavInstruction::Right(value) => write!(f, "R{}", value),
+      NavInstruction::Left(value) => write!(f, "L{}", value),
+    }
+  }
+}
+
 impl NavInstruction {
   fn from_input(instruction: &str, value: i32) -> NavInstruction {
     return match instruction {
@@ -62,200 +89,608 @@ impl NavInstruction {
       _  => panic!("Unrecognized nav instruction {}", instruction),
     }
   } 
+
+  /// How far N/E/S/W instructions move, None for the other instructions
+  fn compass_move(&self) -> Option<Vector> {
+    match self {
+      NavInstruction::North(value) => Some(Direction::North.unit() * *value),
+      NavInstruction::East(value) => Some(Direction::East.unit() * *value),
+      NavInstruction::South(value) => Some(Direction::South.unit() * *value),
+      NavInstruction::West(value) => Some(Direction::West.unit() * *value),
+      _ => None,
+    }
+  }
+}
+
+/// A position or offset, east and north are positive
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct Vector {
+  pub east: i32,
+  pub north: i32,
 }
 
+impl Vector {
+  pub const fn new(east: i32, north: i32) -> Vector {
+    Vector { east, north }
+  }
+
+  /// Rotate clockwise around the origin. Only multiples of 90 degrees keep the vector on the grid
+  pub fn rotate_right(self, degrees: i32) -> Result<Vector, InvalidTurn> {
+    if degrees % 90 != 0 {
+      return Err(InvalidTurn { degrees });
+    }
+    let mut rotated = self;
+    for _ in 0..(degrees / 90).rem_euclid(4) {
+      rotated = Vector::new(rotated.north, -rotated.east);
+    }
+    Ok(rotated)
+  }
+
+  /// Rotate counter-clockwise around the origin
+  pub fn rotate_left(self, degrees: i32) -> Result<Vector, InvalidTurn> {
+    self.rotate_right(-degrees).map_err(|_| InvalidTurn { degrees })
+  }
+
+  /// Distance from the origin moving only along the grid
+  pub fn manhattan(&self) -> i32 {
+    self.east.abs() + self.north.abs()
+  }
+}
+
+impl Add for Vector {
+  type Output = Vector;
+
+  fn add(self, other: Vector) -> Vector {
+    Vector::new(self.east + other.east, self.north + other.north)
+  }
+}
+
+impl Sub for Vector {
+  type Output = Vector;
+
+  fn sub(self, other: Vector) -> Vector {
+    Vector::new(self.east - other.east, self.north - other.north)
+  }
+}
+
+impl AddAssign for Vector {
+  fn add_assign(&mut self, other: Vector) {
+    *self = *self + other;
+  }
+}
+
+impl Mul<i32> for Vector {
+  type Output = Vector;
+
+  fn mul(self, amount: i32) -> Vector {
+    Vector::new(self.east * amount, self.north * amount)
+  }
+}
+
+/// A turn that doesn't end up facing one of the directions the ship can go
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct InvalidTurn {
+  pub degrees: i32,
+}
+
+impl fmt::Display for InvalidTurn {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "can't turn {} degrees, the ship would end up facing off the grid", self.degrees)
+  }
+}
+
+/// How a ship carries out navigation instructions.
+/// New ways of reading the instructions can be added next to `Ship` and `WaypointShip` and picked with `model_by_name`.
+pub trait NavigationModel {
+  fn execute_instruction(&mut self, instruction: &NavInstruction) -> Result<(), InvalidTurn>;
+
+  fn position(&self) -> Vector;
+
+  /// Where the waypoint is relative to the ship, for models that have one
+  fn waypoint(&self) -> Option<Vector> {
+    None
+  }
+
+  fn manhattan_position(&self) -> i32 {
+    self.position().manhattan()
+  }
+}
+
+/// Part 1: compass moves and going forward move the ship, turns change its heading
 #[derive(Debug, PartialEq)]
 pub struct Ship {
-  north: i32,
-  south: i32,
-  east: i32,
-  west: i32,
-  current_direction: Direction,
+  position: Vector,
+  heading: Vector,
 }
 
 impl Ship {
   pub fn new() -> Ship {
-    return Ship {
-      north: 0,
-      south: 0,
-      east: 0,
-      west: 0,
-      current_direction: Direction::East,
+    Ship {
+      position: Vector::default(),
+      heading: Direction::East.unit(),
     }
   }
+}
 
-  fn turn(&mut self, is_left: bool, amount: i32) {
-    let turns = amount / 90;
-    let mut dir_index = DIRECTION_ORDER.iter().position(|val| *val == self.current_direction).unwrap() as i32;
-    for _ in 0..turns {
-      dir_index = if is_left { dir_index - 1 } else { dir_index + 1 };
-      if dir_index < 0 {
-        dir_index = (DIRECTION_ORDER.len() - 1) as i32;
-      } else if dir_index >= DIRECTION_ORDER.len() as i32 {
-        dir_index = 0;
-      }
+impl NavigationModel for Ship {
+  fn execute_instruction(&mut self, instruction: &NavInstruction) -> Result<(), InvalidTurn> {
+    if let Some(offset) = instruction.compass_move() {
+      self.position += offset;
+    }
+    match instruction {
+      NavInstruction::Forward(value) => self.position += self.heading * *value,
+      NavInstruction::Right(value) => self.heading = self.heading.rotate_right(*value)?,
+      NavInstruction::Left(value) => self.heading = self.heading.rotate_left(*value)?,
+      _ => (),
     }
-    self.current_direction = DIRECTION_ORDER[dir_index as usize].clone();
+    Ok(())
   }
 
-  fn go_forward(&mut self, amount: i32) {
-    match self.current_direction {
-      Direction::East => self.east += amount,
-      Direction::North => self.north += amount,
-      Direction::West => self.west += amount,
-      Direction::South => self.south += amount,
+  fn position(&self) -> Vector {
+    self.position
+  }
+}
+
+/// Part 2: compass moves and turns move the waypoint, going forward moves the ship to the waypoint
+#[derive(Debug, PartialEq)]
+pub struct WaypointShip {
+  position: Vector,
+  /// Where the waypoint is relative to the ship
+  waypoint: Vector,
+}
+
+impl WaypointShip {
+  pub fn new() -> WaypointShip {
+    WaypointShip {
+      position: Vector::default(),
+      waypoint: Vector::new(10, 1),
     }
   }
+}
 
-  pub fn execute_instruction(&mut self, instruction: &NavInstruction) {
+impl NavigationModel for WaypointShip {
+  fn execute_instruction(&mut self, instruction: &NavInstruction) -> Result<(), InvalidTurn> {
+    if let Some(offset) = instruction.compass_move() {
+      self.waypoint += offset;
+    }
     match instruction {
-      NavInstruction::North(value) => self.north += value,
-      NavInstruction::East(value) => self.east += value,
-      NavInstruction::South(value) => self.south += value,
-      NavInstruction::West(value) => self.west += value,
-      NavInstruction::Forward(value) => self.go_forward(*value),
-      NavInstruction::Right(value) => self.turn(false, *value),
-      NavInstruction::Left(value) => self.turn(true, *value),
+      NavInstruction::Forward(value) => self.position += self.waypoint * *value,
+      NavInstruction::Right(value) => self.waypoint = self.waypoint.rotate_right(*value)?,
+      NavInstruction::Left(value) => self.waypoint = self.waypoint.rotate_left(*value)?,
+      _ => (),
     }
+    Ok(())
+  }
+
+  fn position(&self) -> Vector {
+    self.position
   }
 
-  pub fn manhattan_position(&self) -> i32 {
-    return (self.north - self.south).abs() + (self.east - self.west).abs();
+  fn waypoint(&self) -> Option<Vector> {
+    Some(self.waypoint)
   }
 }
 
+/// Like `WaypointShip`, but compass moves move the ship itself and the waypoint only ever turns
 #[derive(Debug, PartialEq)]
-pub struct WaypointShip {
-  north: i32,
-  south: i32,
-  east: i32,
-  west: i32,
-  waypoint_1: (Direction, i32),
-  waypoint_2: (Direction, i32),
+pub struct CompassWaypointShip {
+  position: Vector,
+  waypoint: Vector,
 }
 
-impl WaypointShip {
-  pub fn new() -> WaypointShip {
-    return WaypointShip {
-      north: 0,
-      south: 0,
-      east: 0,
-      west: 0,
-      waypoint_1: (Direction::North, 1),
-      waypoint_2: (Direction::East, 10),
+impl CompassWaypointShip {
+  pub fn new() -> CompassWaypointShip {
+    CompassWaypointShip {
+      position: Vector::default(),
+      waypoint: Vector::new(10, 1),
+    }
+  }
+}
+
+impl NavigationModel for CompassWaypointShip {
+  fn execute_instruction(&mut self, instruction: &NavInstruction) -> Result<(), InvalidTurn> {
+    if let Some(offset) = instruction.compass_move() {
+      self.position += offset;
+    }
+    match instruction {
+      NavInstruction::Forward(value) => self.position += self.waypoint * *value,
+      NavInstruction::Right(value) => self.waypoint = self.waypoint.rotate_right(*value)?,
+      NavInstruction::Left(value) => self.waypoint = self.waypoint.rotate_left(*value)?,
+      _ => (),
     }
+    Ok(())
+  }
+
+  fn position(&self) -> Vector {
+    self.position
   }
 
-  fn turn(&mut self, is_left: bool, amount: i32) {
-    let turns = amount / 90;
-    self.waypoint_1 = (self.turn_waypoint(&self.waypoint_1.0, is_left, turns), self.waypoint_1.1);
-    self.waypoint_2 = (self.turn_waypoint(&self.waypoint_2.0, is_left, turns), self.waypoint_2.1);
+  fn waypoint(&self) -> Option<Vector> {
+    Some(self.waypoint)
   }
+}
+
+/// Reads the compass moves relative to where the ship is facing, as if north were straight ahead.
+/// N is forward, S is backwards, and E and W strafe to the right and left.
+#[derive(Debug, PartialEq)]
+pub struct StrafingShip {
+  position: Vector,
+  heading: Vector,
+}
 
-  fn turn_waypoint(&self, starting_dir: &Direction, is_left: bool, turns: i32) -> Direction {
-    let mut dir_index = DIRECTION_ORDER.iter().position(|val| val == starting_dir).unwrap() as i32;
-    for _ in 0..turns {
-      dir_index = if is_left { dir_index - 1 } else { dir_index + 1 };
-      if dir_index < 0 {
-        dir_index = (DIRECTION_ORDER.len() - 1) as i32;
-      } else if dir_index >= DIRECTION_ORDER.len() as i32 {
-        dir_index = 0;
-      }
+impl StrafingShip {
+  pub fn new() -> StrafingShip {
+    StrafingShip {
+      position: Vector::default(),
+      heading: Direction::East.unit(),
     }
-    return DIRECTION_ORDER[dir_index as usize].clone();
   }
+}
 
-  fn go_forward(&mut self, amount: i32) {
-    let waypoints = vec![&self.waypoint_1, &self.waypoint_2];
-    for waypoint in waypoints {
-      match waypoint {
-        (Direction::East, val) => self.east += amount * val,
-        (Direction::North, val) => self.north += amount * val,
-        (Direction::West, val) => self.west += amount * val,
-        (Direction::South, val) => self.south += amount * val,
-      }
+impl NavigationModel for StrafingShip {
+  fn execute_instruction(&mut self, instruction: &NavInstruction) -> Result<(), InvalidTurn> {
+    if let Some(offset) = instruction.compass_move() {
+      // turn the move around so north lines up with the heading
+      let ahead = self.heading;
+      let right = ahead.rotate_right(90)?;
+      self.position += ahead * offset.north + right * offset.east;
+    }
+    match instruction {
+      NavInstruction::Forward(value) => self.position += self.heading * *value,
+      NavInstruction::Right(value) => self.heading = self.heading.rotate_right(*value)?,
+      NavInstruction::Left(value) => self.heading = self.heading.rotate_left(*value)?,
+      _ => (),
     }
+    Ok(())
   }
 
-  fn move_waypoint_ns(&mut self, amount: i32) {
-    self.waypoint_1 = self.calculate_waypoint_ns(&self.waypoint_1, amount);
-    self.waypoint_2 = self.calculate_waypoint_ns(&self.waypoint_2, amount);
+  fn position(&self) -> Vector {
+    self.position
   }
+}
+
+/// Headings a `DiagonalShip` can face, clockwise from north
+const DIAGONAL_HEADINGS: [Vector; 8] = [
+  Vector::new(0, 1), Vector::new(1, 1), Vector::new(1, 0), Vector::new(1, -1),
+  Vector::new(0, -1), Vector::new(-1, -1), Vector::new(-1, 0), Vector::new(-1, 1),
+];
 
-  fn calculate_waypoint_ns(&self, waypoint: &(Direction, i32), amount: i32) -> (Direction, i32) {
-    let mut waypoint_val = waypoint.1;
-    let mut waypiont_dir = waypoint.0.clone();
-    if waypoint.0 == Direction::North {
-      waypoint_val += amount;
-      if waypoint_val < 0 {
-        waypoint_val = waypoint_val.abs();
-        waypiont_dir = Direction::South;
-      }
-    } else if waypoint.0 == Direction::South {
-      waypoint_val -= amount;
-      if waypoint_val < 0 {
-        waypoint_val = waypoint_val.abs();
-        waypiont_dir = Direction::North;
-      }
+/// Like `Ship`, but turns go in steps of 45 degrees so the ship can also face diagonally.
+/// Going forward diagonally moves the ship that far along both axes.
+#[derive(Debug, PartialEq)]
+pub struct DiagonalShip {
+  position: Vector,
+  /// Index into `DIAGONAL_HEADINGS`
+  heading: usize,
+}
+
+impl DiagonalShip {
+  pub fn new() -> DiagonalShip {
+    DiagonalShip {
+      position: Vector::default(),
+      heading: 2,
     }
-    return (waypiont_dir, waypoint_val);
   }
 
-  fn move_waypoint_ew(&mut self, amount: i32) {
-    self.waypoint_1 = self.calculate_waypoint_ew(&self.waypoint_1, amount);
-    self.waypoint_2 = self.calculate_waypoint_ew(&self.waypoint_2, amount);
+  fn turn_right(&mut self, degrees: i32) -> Result<(), InvalidTurn> {
+    if degrees % 45 != 0 {
+      return Err(InvalidTurn { degrees });
+    }
+    self.heading = (self.heading as i32 + degrees / 45).rem_euclid(8) as usize;
+    Ok(())
   }
+}
 
-  fn calculate_waypoint_ew(&self, waypoint: &(Direction, i32), amount: i32) -> (Direction, i32) {
-    let mut waypoint_val = waypoint.1;
-    let mut waypiont_dir = waypoint.0.clone();
-    if waypoint.0 == Direction::East {
-      waypoint_val += amount;
-      if waypoint_val < 0 {
-        waypoint_val = waypoint_val.abs();
-        waypiont_dir = Direction::West;
-      }
-    } else if waypoint.0 == Direction::West {
-      waypoint_val -= amount;
-      if waypoint_val < 0 {
-        waypoint_val = waypoint_val.abs();
-        waypiont_dir = Direction::East;
-      }
+impl NavigationModel for DiagonalShip {
+  fn execute_instruction(&mut self, instruction: &NavInstruction) -> Result<(), InvalidTurn> {
+    if let Some(offset) = instruction.compass_move() {
+      self.position += offset;
+    }
+    match instruction {
+      NavInstruction::Forward(value) => self.position += DIAGONAL_HEADINGS[self.heading] * *value,
+      NavInstruction::Right(value) => self.turn_right(*value)?,
+      NavInstruction::Left(value) => self.turn_right(-value).map_err(|_| InvalidTurn { degrees: *value })?,
+      _ => (),
     }
-    return (waypiont_dir, waypoint_val);
+    Ok(())
   }
 
-  pub fn execute_instruction(&mut self, instruction: &NavInstruction) {
-    match instruction {
-      NavInstruction::North(value) => self.move_waypoint_ns(*value),
-      NavInstruction::East(value) => self.move_waypoint_ew(*value),
-      NavInstruction::South(value) => self.move_waypoint_ns(-value),
-      NavInstruction::West(value) => self.move_waypoint_ew(-value),
-      NavInstruction::Forward(value) => self.go_forward(*value),
-      NavInstruction::Right(value) => self.turn(false, *value),
-      NavInstruction::Left(value) => self.turn(true, *value),
+  fn position(&self) -> Vector {
+    self.position
+  }
+}
+
+/// Names `model_by_name` knows about
+pub const MODEL_NAMES: [&str; 5] = ["ship", "waypoint", "compass-waypoint", "strafe", "diagonal"];
+
+/// A fresh ship using the named navigation model
+pub fn model_by_name(name: &str) -> Option<Box<dyn NavigationModel>> {
+  match name {
+    "ship" => Some(Box::new(Ship::new())),
+    "waypoint" => Some(Box::new(WaypointShip::new())),
+    "compass-waypoint" => Some(Box::new(CompassWaypointShip::new())),
+    "strafe" => Some(Box::new(StrafingShip::new())),
+    "diagonal" => Some(Box::new(DiagonalShip::new())),
+    _ => None,
+  }
+}
+
+/// Follow all the instructions, returning how far the ship ends up from where it started
+pub fn navigate(ship: &mut dyn NavigationModel, instructions: &[NavInstruction]) -> Result<i32, InvalidTurn> {
+  for instruction in instructions {
+    ship.execute_instruction(instruction)?;
+  }
+  Ok(ship.manhattan_position())
+}
+
+pub fn navigate_and_get_position(instructions: &[NavInstruction]) -> Result<i32, InvalidTurn> {
+  navigate(&mut Ship::new(), instructions)
+}
+
+pub fn naviage_using_waypoint(instructions: &[NavInstruction]) -> Result<i32, InvalidTurn> {
+  navigate(&mut WaypointShip::new(), instructions)
+}
+
+/// Where the ship was after each instruction, starting from where it set off
+pub struct Track {
+  pub positions: Vec<Vector>,
+  /// Where the waypoint was on the map after each instruction, empty for ships without one
+  pub waypoints: Vec<Vector>,
+}
+
+impl Track {
+  /// The south west and north east corners of the smallest box holding the ship's path
+  pub fn bounding_box(&self) -> (Vector, Vector) {
+    bounds(&self.positions)
+  }
+
+  /// How far the ship moved along the grid in total, not just how far it ended up from the start
+  pub fn distance_travelled(&self) -> i32 {
+    self.positions.windows(2)
+      .map(|pair| (pair[1] - pair[0]).manhattan())
+      .sum()
+  }
+
+  /// Draws the path as an SVG, with north at the top. The waypoint is drawn dashed
+  pub fn to_svg(&self) -> String {
+    let (low, high) = bounds(&[self.positions.as_slice(), self.waypoints.as_slice()].concat());
+    let margin = ((high.east - low.east).max(high.north - low.north) / 20).max(1);
+    let mut svg = format!(
+      "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+      low.east - margin, -high.north - margin, high.east - low.east + 2 * margin, high.north - low.north + 2 * margin);
+    if !self.waypoints.is_empty() {
+      svg.push_str(&format!(
+        "  <polyline points=\"{}\" fill=\"none\" stroke=\"gray\" stroke-dasharray=\"4\" vector-effect=\"non-scaling-stroke\"/>\n",
+        svg_points(&self.waypoints)));
+    }
+    svg.push_str(&format!(
+      "  <polyline points=\"{}\" fill=\"none\" stroke=\"navy\" stroke-width=\"2\" vector-effect=\"non-scaling-stroke\"/>\n",
+      svg_points(&self.positions)));
+    if let (Some(start), Some(end)) = (self.positions.first(), self.positions.last()) {
+      svg.push_str(&format!("  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"green\"/>\n", start.east, -start.north, margin));
+      svg.push_str(&format!("  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"red\"/>\n", end.east, -end.north, margin));
     }
+    svg.push_str("</svg>\n");
+    svg
   }
 
-  pub fn manhattan_position(&self) -> i32 {
-    return (self.north - self.south).abs() + (self.east - self.west).abs();
+  /// The path as a GeoJSON feature collection, using east and north as x and y
+  pub fn to_geojson(&self) -> String {
+    let mut features = vec![geojson_line("ship", &self.positions)];
+    if !self.waypoints.is_empty() {
+      features.push(geojson_line("waypoint", &self.waypoints));
+    }
+    format!("{{\"type\":\"FeatureCollection\",\"features\":[{}]}}", features.join(","))
   }
 }
 
-pub fn navigate_and_get_position(instructions: &Vec<NavInstruction>) -> i32 {
+fn bounds(points: &[Vector]) -> (Vector, Vector) {
+  let start = points.first().copied().unwrap_or_default();
+  points.iter().fold((start, start), |(low, high), point| (
+    Vector::new(low.east.min(point.east), low.north.min(point.north)),
+    Vector::new(high.east.max(point.east), high.north.max(point.north)),
+  ))
+}
+
+fn svg_points(path: &[Vector]) -> String {
+  path.iter()
+    .map(|point| format!("{},{}", point.east, -point.north))
+    .collect::<Vec<String>>()
+    .join(" ")
+}
+
+fn geojson_line(name: &str, path: &[Vector]) -> String {
+  let coordinates = path.iter()
+    .map(|point| format!("[{},{}]", point.east, point.north))
+    .collect::<Vec<String>>();
+  format!(
+    "{{\"type\":\"Feature\",\"properties\":{{\"name\":\"{}\"}},\"geometry\":{{\"type\":\"LineString\",\"coordinates\":[{}]}}}}",
+    name, coordinates.join(","))
+}
+
+/// Follow the instructions, keeping track of where the ship and any waypoint it has go
+pub fn record_track_with(ship: &mut dyn NavigationModel, instructions: &[NavInstruction]) -> Result<Track, InvalidTurn> {
+  let mut track = Track { positions: Vec::new(), waypoints: Vec::new() };
+  let mut record = |ship: &dyn NavigationModel| {
+    track.positions.push(ship.position());
+    if let Some(waypoint) = ship.waypoint() {
+      track.waypoints.push(ship.position() + waypoint);
+    }
+  };
+  record(ship);
+  for instruction in instructions {
+    ship.execute_instruction(instruction)?;
+    record(ship);
+  }
+  Ok(track)
+}
+
+pub fn record_track(instructions: &[NavInstruction]) -> Result<Track, InvalidTurn> {
+  record_track_with(&mut Ship::new(), instructions)
+}
+
+pub fn record_waypoint_track(instructions: &[NavInstruction]) -> Result<Track, InvalidTurn> {
+  record_track_with(&mut WaypointShip::new(), instructions)
+}
+
+/// The shortest list of instructions that takes a `Ship` with the given heading, like `Direction::East.unit()`,
+/// from the start to `target`. The ship can go straight to any point with at most one move along each axis.
+pub fn plan_route(heading: Vector, target: Vector) -> Vec<NavInstruction> {
+  compass_moves(target).into_iter()
+    .map(|instruction| match instruction.compass_move() {
+      // moving the way the ship already faces reads better as going forward
+      Some(offset) if offset == heading * offset.manhattan() => NavInstruction::Forward(offset.manhattan()),
+      _ => instruction,
+    })
+    .collect()
+}
+
+/// The shortest list of instructions that takes a `WaypointShip` with the waypoint at `waypoint`
+/// from the start to `target`. Only moving forward moves the ship, so this looks for the fewest
+/// changes to the waypoint that let a number of trips land exactly on the target.
+pub fn plan_waypoint_route(waypoint: Vector, target: Vector) -> Vec<NavInstruction> {
+  if target == Vector::default() {
+    return Vec::new();
+  }
+  if let Some(times) = trips_to_reach(waypoint, target) {
+    return vec![NavInstruction::Forward(times)];
+  }
+  for turn in [NavInstruction::Right(90), NavInstruction::Right(180), NavInstruction::Left(90)] {
+    if let Some(times) = turn_vector(waypoint, &turn).and_then(|turned| trips_to_reach(turned, target)) {
+      return vec![turn, NavInstruction::Forward(times)];
+    }
+  }
+  // moving the waypoint along one axis works if the other axis already lines up
+  let north_only = trips_along_axis(waypoint.east, target.east, target.north)
+    .map(|times| (times, Vector::new(0, target.north / times - waypoint.north)));
+  let east_only = trips_along_axis(waypoint.north, target.north, target.east)
+    .map(|times| (times, Vector::new(target.east / times - waypoint.east, 0)));
+  if let Some((times, shift)) = north_only.or(east_only) {
+    let mut route = compass_moves(shift);
+    route.push(NavInstruction::Forward(times));
+    return route;
+  }
+  let mut route = compass_moves(target - waypoint);
+  route.push(NavInstruction::Forward(1));
+  route
+}
+
+/// A shorter list of instructions that leaves a `Ship` in the same place facing the same way,
+/// or the original instructions if they can't be shortened
+pub fn simplify(instructions: &[NavInstruction]) -> Result<Vec<NavInstruction>, InvalidTurn> {
   let mut ship = Ship::new();
   for instruction in instructions {
-    ship.execute_instruction(instruction);
+    ship.execute_instruction(instruction)?;
   }
-  return ship.manhattan_position();
+  let start = Ship::new();
+  let mut simplified = plan_route(start.heading, ship.position);
+  simplified.extend(turns_between(start.heading, ship.heading));
+  Ok(shortest(instructions, vec![simplified]))
 }
 
-pub fn naviage_using_waypoint(instructions: &Vec<NavInstruction>) -> i32 {
+/// A shorter list of instructions that leaves a `WaypointShip` in the same place with the waypoint
+/// in the same spot, or the original instructions if they can't be shortened
+pub fn simplify_waypoint(instructions: &[NavInstruction]) -> Result<Vec<NavInstruction>, InvalidTurn> {
   let mut ship = WaypointShip::new();
   for instruction in instructions {
-    ship.execute_instruction(instruction);
+    ship.execute_instruction(instruction)?;
+  }
+  let start = WaypointShip::new();
+  let mut candidates = Vec::new();
+
+  // get to the end, then put the waypoint back where it finished
+  let mut route = plan_waypoint_route(start.waypoint, ship.position);
+  let mut replay = WaypointShip::new();
+  for instruction in &route {
+    replay.execute_instruction(instruction)?;
+  }
+  route.extend(waypoint_moves(replay.waypoint, ship.waypoint));
+  candidates.push(route);
+
+  // or set up the final waypoint first if the trips all use it
+  if let Some(times) = trips_to_reach(ship.waypoint, ship.position) {
+    let mut route = waypoint_moves(start.waypoint, ship.waypoint);
+    route.push(NavInstruction::Forward(times));
+    candidates.push(route);
+  } else if ship.position == start.position {
+    candidates.push(waypoint_moves(start.waypoint, ship.waypoint));
   }
-  return ship.manhattan_position();
+  Ok(shortest(instructions, candidates))
+}
+
+fn shortest(original: &[NavInstruction], candidates: Vec<Vec<NavInstruction>>) -> Vec<NavInstruction> {
+  // the first of equally short routes wins, so keep the original unless there's something better
+  std::iter::once(original.to_vec())
+    .chain(candidates)
+    .min_by_key(|route| route.len())
+    .unwrap()
+}
+
+/// One east/west move and one north/south move covering the offset, skipping either if it's 0
+fn compass_moves(offset: Vector) -> Vec<NavInstruction> {
+  let mut moves = Vec::new();
+  if offset.east > 0 {
+    moves.push(NavInstruction::East(offset.east));
+  } else if offset.east < 0 {
+    moves.push(NavInstruction::West(-offset.east));
+  }
+  if offset.north > 0 {
+    moves.push(NavInstruction::North(offset.north));
+  } else if offset.north < 0 {
+    moves.push(NavInstruction::South(-offset.north));
+  }
+  moves
+}
+
+fn turn_vector(vector: Vector, turn: &NavInstruction) -> Option<Vector> {
+  match turn {
+    NavInstruction::Right(degrees) => vector.rotate_right(*degrees).ok(),
+    NavInstruction::Left(degrees) => vector.rotate_left(*degrees).ok(),
+    _ => None,
+  }
+}
+
+/// The single turn, if any, from one heading to another
+fn turns_between(from: Vector, to: Vector) -> Vec<NavInstruction> {
+  [NavInstruction::Right(90), NavInstruction::Right(180), NavInstruction::Left(90)].iter().copied()
+    .filter(|turn| from != to && turn_vector(from, turn) == Some(to))
+    .take(1)
+    .collect()
+}
+
+/// Instructions that move the waypoint, turning it if that's all it takes
+fn waypoint_moves(from: Vector, to: Vector) -> Vec<NavInstruction> {
+  let turns = turns_between(from, to);
+  if turns.is_empty() {
+    compass_moves(to - from)
+  } else {
+    turns
+  }
+}
+
+/// How many trips to the waypoint land exactly on the target
+fn trips_to_reach(waypoint: Vector, target: Vector) -> Option<i32> {
+  let times = if waypoint.east != 0 {
+    target.east / waypoint.east
+  } else if waypoint.north != 0 {
+    target.north / waypoint.north
+  } else {
+    return None;
+  };
+  if times > 0 && waypoint * times == target { Some(times) } else { None }
+}
+
+/// How many trips land on the target after moving the waypoint along the other axis,
+/// where `fixed` is the waypoint's position along the axis that isn't moved
+fn trips_along_axis(fixed: i32, target_fixed: i32, target_other: i32) -> Option<i32> {
+  let times = if fixed == 0 && target_fixed == 0 {
+    1
+  } else if fixed != 0 && target_fixed % fixed == 0 {
+    target_fixed / fixed
+  } else {
+    return None;
+  };
+  if times > 0 && target_other % times == 0 { Some(times) } else { None }
 }
 
 pub fn parse_instructions(input: &str) -> Vec<NavInstruction>{
@@ -1056,25 +1491,146 @@ pub fn read_input() -> String {
 mod tests {
   use super::*;
 
-  #[test]
-  fn test_nav() {
-    let input = "F10
+  const EXAMPLE: &str = "F10
       N3
       F7
       R90
       F11";
-    let instructions = parse_instructions(input);
-    assert_eq!(25, navigate_and_get_position(&instructions));
+
+  fn sail<M: NavigationModel>(mut ship: M, instructions: &[NavInstruction]) -> M {
+    for instruction in instructions {
+      ship.execute_instruction(instruction).unwrap();
+    }
+    ship
+  }
+
+  #[test]
+  fn test_nav() {
+    let instructions = parse_instructions(EXAMPLE);
+    assert_eq!(Ok(25), navigate_and_get_position(&instructions));
   }
 
   #[test]
   fn test_waypoint() {
-    let input = "F10
-      N3
-      F7
+    let instructions = parse_instructions(EXAMPLE);
+    assert_eq!(Ok(286), naviage_using_waypoint(&instructions));
+  }
+
+  #[test]
+  fn test_tracks() {
+    let instructions = parse_instructions(EXAMPLE);
+    let track = record_track(&instructions).unwrap();
+    assert_eq!(vec![Vector::new(0, 0), Vector::new(10, 0), Vector::new(10, 3), Vector::new(17, 3), Vector::new(17, 3), Vector::new(17, -8)], track.positions);
+    assert_eq!((Vector::new(0, -8), Vector::new(17, 3)), track.bounding_box());
+    assert_eq!(31, track.distance_travelled());
+
+    let track = record_waypoint_track(&instructions).unwrap();
+    assert_eq!(Vector::new(214, -72), *track.positions.last().unwrap());
+    assert_eq!(Vector::new(218, -82), *track.waypoints.last().unwrap());
+    assert_eq!((Vector::new(0, -72), Vector::new(214, 38)), track.bounding_box());
+    // 100 + 10, 70 + 28, 44 + 110
+    assert_eq!(362, track.distance_travelled());
+  }
+
+  #[test]
+  fn export_track() {
+    let track = record_track(&parse_instructions("F2\n  N1")).unwrap();
+    assert_eq!(
+      "{\"type\":\"FeatureCollection\",\"features\":[{\"type\":\"Feature\",\"properties\":{\"name\":\"ship\"},\"geometry\":{\"type\":\"LineString\",\"coordinates\":[[0,0],[2,0],[2,1]]}}]}",
+      track.to_geojson());
+    let svg = track.to_svg();
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-1 -2 4 3\">"));
+    assert!(svg.contains("points=\"0,0 2,0 2,-1\""));
+    assert!(!svg.contains("stroke-dasharray"));
+  }
+
+  #[test]
+  fn plan_routes() {
+    assert_eq!(vec![NavInstruction::Forward(17), NavInstruction::South(8)], plan_route(Direction::East.unit(), Vector::new(17, -8)));
+    assert_eq!(vec![NavInstruction::West(3)], plan_route(Direction::North.unit(), Vector::new(-3, 0)));
+    assert!(plan_route(Direction::North.unit(), Vector::default()).is_empty());
+
+    let waypoint = Vector::new(10, 1);
+    assert_eq!(vec![NavInstruction::Forward(10)], plan_waypoint_route(waypoint, Vector::new(100, 10)));
+    assert_eq!(vec![NavInstruction::Right(90), NavInstruction::Forward(3)], plan_waypoint_route(waypoint, Vector::new(3, -30)));
+    assert_eq!(vec![NavInstruction::North(3), NavInstruction::Forward(7)], plan_waypoint_route(waypoint, Vector::new(70, 28)));
+    assert_eq!(vec![NavInstruction::North(1), NavInstruction::Forward(1)], plan_waypoint_route(waypoint, Vector::new(10, 2)));
+    assert_eq!(vec![NavInstruction::West(5), NavInstruction::Forward(3)], plan_waypoint_route(waypoint, Vector::new(15, 3)));
+    let route = plan_waypoint_route(waypoint, Vector::new(214, -72));
+    assert_eq!(vec![NavInstruction::East(204), NavInstruction::South(73), NavInstruction::Forward(1)], route);
+    assert_eq!(Vector::new(214, -72), sail(WaypointShip::new(), &route).position());
+  }
+
+  #[test]
+  fn simplify_instructions() {
+    let instructions = parse_instructions(EXAMPLE);
+    let simplified = simplify(&instructions).unwrap();
+    assert_eq!("F17 S8 R90", simplified.iter().map(|i| i.to_string()).collect::<Vec<String>>().join(" "));
+    assert_eq!(sail(Ship::new(), &instructions), sail(Ship::new(), &simplified));
+    // nothing shorter ends up in the same place with the same waypoint
+    assert_eq!(instructions, simplify_waypoint(&instructions).unwrap());
+
+    let instructions = parse_instructions("N3
+      S3
+      F10
       R90
-      F11";
-    let instructions = parse_instructions(input);
-    assert_eq!(286, naviage_using_waypoint(&instructions));
+      L90
+      F10
+      E0");
+    assert_eq!(vec![NavInstruction::Forward(20)], simplify_waypoint(&instructions).unwrap());
+    let instructions = parse_instructions("L90
+      F2
+      R180
+      F2
+      L90
+      E4");
+    let simplified = simplify_waypoint(&instructions).unwrap();
+    assert_eq!(vec![NavInstruction::East(4)], simplified);
+    assert_eq!(sail(WaypointShip::new(), &instructions), sail(WaypointShip::new(), &simplified));
+  }
+
+  #[test]
+  fn other_models() {
+    let instructions = parse_instructions(EXAMPLE);
+    let distances: Vec<Result<i32, InvalidTurn>> = MODEL_NAMES.iter()
+      .map(|name| navigate(model_by_name(name).unwrap().as_mut(), &instructions))
+      .collect();
+    assert_eq!(vec![Ok(25), Ok(286), Ok(271), Ok(31), Ok(25)], distances);
+    assert!(model_by_name("submarine").is_none());
+
+    // strafing left while facing east heads north
+    let mut ship = StrafingShip::new();
+    ship.execute_instruction(&NavInstruction::West(2)).unwrap();
+    assert_eq!(Vector::new(0, 2), ship.position());
+
+    let mut ship = DiagonalShip::new();
+    let diagonal = parse_instructions("R45
+      F2
+      L90
+      F1");
+    assert_eq!(Ok(4), navigate(&mut ship, &diagonal));
+    assert_eq!(Vector::new(3, -1), ship.position());
+    assert_eq!(Err(InvalidTurn { degrees: 30 }), ship.execute_instruction(&NavInstruction::Left(30)));
+  }
+
+  #[test]
+  fn rotate_vector() {
+    let waypoint = Vector::new(10, 4);
+    assert_eq!(Ok(Vector::new(4, -10)), waypoint.rotate_right(90));
+    assert_eq!(Ok(Vector::new(-4, 10)), waypoint.rotate_left(90));
+    assert_eq!(Ok(Vector::new(-10, -4)), waypoint.rotate_left(180));
+    assert_eq!(waypoint.rotate_right(270), waypoint.rotate_left(90));
+    assert_eq!(waypoint.rotate_right(-90), waypoint.rotate_left(450));
+    assert_eq!(Ok(waypoint), waypoint.rotate_right(360));
+    assert_eq!(Err(InvalidTurn { degrees: 45 }), waypoint.rotate_left(45));
+  }
+
+  #[test]
+  fn uneven_turns() {
+    let instructions = parse_instructions("F10
+      R45
+      F5");
+    assert_eq!(Err(InvalidTurn { degrees: 45 }), navigate_and_get_position(&instructions));
+    assert_eq!(Err(InvalidTurn { degrees: 45 }), naviage_using_waypoint(&instructions));
   }
 }
\ No newline at end of file
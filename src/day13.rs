@@ -21,52 +21,151 @@
   What is the earliest timestamp such that all of the listed bus IDs depart at offsets matching their positions in the list?
 */
 
-pub fn earliest_bus(start_time: i32, bus_ids: &Vec<i32>) -> i32 {
-  let mut earliest_id = 0;
-  let mut earliest_time = 50000;
-  for &id in bus_ids {
-    let time = id - (start_time % id);
-    if time < earliest_time {
-      earliest_time = time;
-      earliest_id = id;
+use crate::number_theory;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
+/// The ID of the first bus to leave at or after the start time, multiplied by how long it is to wait for it.
+/// None if there are no buses.
+pub fn earliest_bus(start_time: i32, bus_ids: &[i32]) -> Option<i32> {
+  bus_ids.iter()
+    .filter_map(|&id| Some((id, wait_for(id as i64, start_time as i64)? as i32)))
+    .min_by_key(|&(_, wait)| wait)
+    .map(|(id, wait)| id * wait)
+}
+
+/// Minutes from time until the bus next leaves, 0 if it's leaving right then.
+/// None for a bus ID of 0 or less, which never comes back round to leave.
+fn wait_for(bus: i64, time: i64) -> Option<i64> {
+  if bus <= 0 {
+    return None;
+  }
+  time.checked_neg()?.checked_rem_euclid(bus)
+}
+
+/// A bus leaving at a time
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Departure {
+  pub bus: i64,
+  pub time: i64,
+}
+
+/// The buses in service, each with how many minutes after the first bus it's meant to leave
+pub struct Schedule {
+  buses: Vec<(i64, i64)>,
+}
+
+impl Schedule {
+  /// Takes (bus id, offset) pairs, as from `parse_input_with_offsets`
+  pub fn new(buses: Vec<(i64, i64)>) -> Schedule {
+    Schedule { buses }
+  }
+
+  /// When each bus next leaves at or after time, soonest first
+  pub fn next_departures(&self, time: i64) -> Vec<Departure> {
+    let mut departures = self.buses.iter()
+      .filter_map(|&(bus, _)| Some(Departure { bus, time: time + wait_for(bus, time)? }))
+      .collect::<Vec<Departure>>();
+    departures.sort_by_key(|departure| (departure.time, departure.bus));
+    departures
+  }
+
+  /// Every departure within the window, in order
+  pub fn timetable(&self, window: Range<i64>) -> Vec<Departure> {
+    let mut departures = Vec::new();
+    for &(bus, _) in &self.buses {
+      let mut time = match wait_for(bus, window.start) {
+        Some(wait) => window.start + wait,
+        None => continue,
+      };
+      while time < window.end {
+        departures.push(Departure { bus, time });
+        time += bus;
+      }
+    }
+    departures.sort_by_key(|departure| (departure.time, departure.bus));
+    departures
+  }
+
+  /// The first time at or after `after` when all of the given buses leave together.
+  /// None if there are no buses, one of them isn't in the schedule or never leaves (see `wait_for`),
+  /// or the time is too far away to count to.
+  pub fn depart_together(&self, buses: &[i64], after: i64) -> Option<i64> {
+    let leaves = |bus: i64| wait_for(bus, 0).is_some() && self.buses.iter().any(|&(scheduled, _)| scheduled == bus);
+    if buses.is_empty() || !buses.iter().all(|&bus| leaves(bus)) {
+      return None;
+    }
+    let period = buses.iter().try_fold(1, |period, &bus| number_theory::lcm(period, bus as i128))?;
+    if period == 0 {
+      return None;
     }
+    let time = (after as i128).checked_add((-(after as i128)).rem_euclid(period))?;
+    i64::try_from(time).ok()
   }
-  return earliest_id * earliest_time;
+
+  /// The first time at or after `after` when each bus leaves its offset after the first one.
+  /// None if no time works.
+  pub fn first_offset_time_after(&self, after: i128) -> Option<Timestamp> {
+    match self.first_offset_time_small(after) {
+      Some(time) => Some(Timestamp::Small(time)),
+      None => self.first_offset_time_big(&BigInt::from(after)).map(Timestamp::from),
+    }
+  }
+
+  fn first_offset_time_small(&self, after: i128) -> Option<i128> {
+    let congruences = self.buses.iter()
+      .map(|&(bus, offset)| (-(offset as i128), bus as i128))
+      .collect::<Vec<(i128, i128)>>();
+    let (first, period) = number_theory::crt(&congruences)?;
+    after.checked_add(first.checked_sub(after)?.rem_euclid(period))
+  }
+
+  /// Same as `first_offset_time_small`, for when the product of the bus IDs doesn't fit in an i128
+  fn first_offset_time_big(&self, after: &BigInt) -> Option<BigInt> {
+    let congruences = self.buses.iter()
+      .map(|&(bus, offset)| (BigInt::from(-offset), BigInt::from(bus)))
+      .collect::<Vec<(BigInt, BigInt)>>();
+    let (first, period) = number_theory::crt_big(&congruences)?;
+    let wait = (first - after) % &period;
+    Some(if wait < BigInt::from(0) { after + wait + period } else { after + wait })
+  }
+}
+
+/// The minute when the buses line up with their offsets. It's a plain i128 unless the bus IDs multiply out
+/// past i128::MAX or the search starts close to it, in which case `first_offset_time_after` solves it again with BigInt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Timestamp {
+  Small(i128),
+  Big(BigInt),
 }
 
-  // Solve using Chinese Remainder Theory 
-  // Given x | a (mod n) ---> all values of x such that x % n = a or rather (x - a) % n = 0
-  
-  // A sequcnce can be derived such that
-  // x | a1 (mod n1)
-  // x | a2 (mod n2)
-  // x2 = a1 * m2 * n2 + a2 * m1 * n1
-  //    where m1 and m2 are the Bézout coefficients of n1 and n2
-  //    and n1 and n2 are coprime
-  // This can be reformulated as
-  //    x | x2 (mod n1*n2)
-pub fn find_first_contiguous_time(bus_ids_with_offsets: &Vec<(i64, i64)>) -> i128 {
-  let first = bus_ids_with_offsets[0];
-  let mut n0 = first.0 as i128; // bus id
-  let mut a0 = -1 * first.1 as i128; // offset seconds from final timestamp
-  for i in 1..bus_ids_with_offsets.len() {
-    let ni = bus_ids_with_offsets[i].0 as i128;
-    let ai = -1 * bus_ids_with_offsets[i].1 as i128;
-    let (m0, mi) = bezout_coefficients(n0, ni);
-    let xi = a0 * ni * mi + ai * n0 * m0;
-    n0 = n0 * ni;
-    a0 = xi;
-
-    // of the many valid values of x, use the one closest to 0
-    if a0.abs() > n0 {
-      let over = a0 / n0;
-      a0 = a0 - over * n0;
+impl From<BigInt> for Timestamp {
+  fn from(time: BigInt) -> Timestamp {
+    match time.to_i128() {
+      Some(time) => Timestamp::Small(time),
+      None => Timestamp::Big(time),
     }
   }
-  if a0 < 0 {
-    return a0 + n0;
+}
+
+impl fmt::Display for Timestamp {
+  // hand the formatter on, so a width lines up small and big timestamps the same way in a table
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Timestamp::Small(time) => fmt::Display::fmt(time, f),
+      Timestamp::Big(time) => fmt::Display::fmt(time, f),
+    }
   }
-  return a0;
+}
+
+/// The earliest timestamp where each bus departs its offset in minutes after the first one,
+/// solving x = -offset (mod id) for every bus with the Chinese Remainder Theorem.
+/// None if no timestamp works, which can happen when bus IDs share a factor.
+pub fn find_first_contiguous_time(bus_ids_with_offsets: &[(i64, i64)]) -> Option<Timestamp> {
+  Schedule::new(bus_ids_with_offsets.to_vec()).first_offset_time_after(0)
 }
 
 pub fn read_input() -> String {
@@ -97,33 +196,6 @@ pub fn parse_input_with_offsets(input: &str) -> Vec<(i64, i64)> {
   return ids_with_offsets;
 }
 
-// see: https://en.wikipedia.org/wiki/Extended_Euclidean_algorithm#Pseudocode
-fn bezout_coefficients(a: i128, b: i128) -> (i128, i128) {
-  let mut s = 0;
-  let mut old_s = 1;
-  let mut r = b;
-  let mut old_r = a;
-      
-  while r != 0 {
-    let quotient = old_r / r;
-    let new_r = old_r - quotient * r;
-    let new_s = old_s - quotient * s;
-    old_r = r;
-    r = new_r;
-    old_s = s;
-    s = new_s;
-  }
-
-  let bezout_t;
-  if b != 0 {
-    bezout_t = (old_r - old_s * a) / b;
-  } else {
-    bezout_t = 0;
-  }
-  // Note: Greatest Common Divisor = old_r
-  return (old_s, bezout_t);
-}
-
 
 #[cfg(test)]
 mod tests {
@@ -134,7 +206,12 @@ mod tests {
     let input = "939
       7,13,x,x,59,x,31,19";
     let parse = parse_input_start_time(input);
-    assert_eq!(295, earliest_bus(parse.0, &parse.1));
+    assert_eq!(Some(295), earliest_bus(parse.0, &parse.1));
+    assert_eq!(None, earliest_bus(939, &[]));
+    // bus 7 is leaving right now
+    assert_eq!(Some(0), earliest_bus(945, &parse.1));
+    assert_eq!(Some(28), earliest_bus(10, &[0, 7]));
+    assert_eq!(None, earliest_bus(10, &[0]));
   }
 
   #[test]
@@ -142,16 +219,72 @@ mod tests {
     let input = "939
       7,13,x,x,59,x,31,19";
     let parse = parse_input_with_offsets(&input);
-    assert_eq!(1068781, find_first_contiguous_time(&parse));
+    assert_eq!(Some(Timestamp::Small(1068781)), find_first_contiguous_time(&parse));
   }
-  
+
+  #[test]
+  fn test_timetable() {
+    let schedule = Schedule::new(parse_input_with_offsets("939
+      7,13,x,x,59,x,31,19"));
+    let next = schedule.next_departures(939);
+    assert_eq!(Departure { bus: 59, time: 944 }, next[0]);
+    assert_eq!(vec![944, 945, 949, 950, 961], next.iter().map(|departure| departure.time).collect::<Vec<i64>>());
+
+    assert_eq!(vec![
+      Departure { bus: 7, time: 0 }, Departure { bus: 13, time: 0 }, Departure { bus: 19, time: 0 },
+      Departure { bus: 31, time: 0 }, Departure { bus: 59, time: 0 },
+      Departure { bus: 7, time: 7 }, Departure { bus: 13, time: 13 }, Departure { bus: 7, time: 14 },
+    ], schedule.timetable(0..15));
+    assert!(schedule.timetable(10..10).is_empty());
+
+    assert_eq!(Some(91), schedule.depart_together(&[7, 13], 1));
+    assert_eq!(Some(182), schedule.depart_together(&[7, 13], 92));
+    assert_eq!(Some(0), schedule.depart_together(&[7, 13, 59, 31, 19], 0));
+    assert_eq!(Some(3162341), schedule.depart_together(&[7, 13, 59, 31, 19], 1));
+    assert_eq!(None, schedule.depart_together(&[7, 5], 0));
+
+    let with_zero = Schedule::new(vec![(0, 0), (7, 1), (-3, 2)]);
+    assert_eq!(vec![Departure { bus: 7, time: 14 }], with_zero.next_departures(10));
+    assert_eq!(vec![Departure { bus: 7, time: 14 }], with_zero.timetable(10..20));
+    assert_eq!(None, with_zero.depart_together(&[-3], 10));
+    assert_eq!(None, with_zero.depart_together(&[0, 7], 10));
+    assert_eq!(Some(14), with_zero.depart_together(&[7], 10));
+    assert_eq!(None, schedule.depart_together(&[], 10));
+
+    assert_eq!(Some(Timestamp::Small(1068781)), schedule.first_offset_time_after(0));
+    assert_eq!(Some(Timestamp::Small(1068781)), schedule.first_offset_time_after(1068781));
+    assert_eq!(Some(Timestamp::Small(1068781 + 3162341)), schedule.first_offset_time_after(1068782));
+  }
+
+  #[test]
+  fn test_big_schedule() {
+    let buses = [1000000007, 1000000009, 998244353, 1000000021, 1000000033];
+    let schedule = buses.iter().enumerate().map(|(offset, &bus)| (bus, offset as i64)).collect::<Vec<(i64, i64)>>();
+    let time = match find_first_contiguous_time(&schedule) {
+      Some(Timestamp::Big(time)) => time,
+      other => panic!("expected a big timestamp, got {:?}", other),
+    };
+    for (offset, &bus) in buses.iter().enumerate() {
+      assert_eq!(BigInt::from(0), (&time + offset) % bus);
+    }
+    let period = buses.iter().fold(BigInt::from(1), |period, &bus| period * bus);
+    assert!(time < period);
+    assert_eq!(time.to_string(), find_first_contiguous_time(&schedule).unwrap().to_string());
+
+    // small buses, but the next time after a late start is past what fits in an i128
+    let schedule = Schedule::new(vec![(7, 0), (13, 1)]);
+    let expected = BigInt::from(i128::MAX) + 41;
+    assert_eq!(Some(Timestamp::Big(expected)), schedule.first_offset_time_after(i128::MAX - 5));
+    assert_eq!(Some(Timestamp::Small(77)), schedule.first_offset_time_after(0));
+    assert_eq!("      77", format!("{:>8}", Timestamp::Small(77)));
+    assert_eq!("      170141183460469231731687303715884105768", format!("{:>45}", schedule.first_offset_time_after(i128::MAX - 5).unwrap()));
+  }
+
   #[test]
-  fn test_gcd() {
-    assert_eq!((-9,2), bezout_coefficients(15, 69));
-    assert_eq!((2,-1), bezout_coefficients(7, 13));
-    assert_eq!((1,19), bezout_coefficients(77, -4));
-    assert_eq!((1,-3), bezout_coefficients(-14, -4));
-    assert_eq!((-3,1), bezout_coefficients(-4, -14));
-    assert_eq!((24,-37), bezout_coefficients(91, 59));
+  fn test_shared_factors() {
+    // bus 4 leaves at t and bus 6 at t + 2
+    assert_eq!(Some(Timestamp::Small(4)), find_first_contiguous_time(&parse_input_with_offsets("0\n4,x,6")));
+    // t is even for bus 4, but t + 1 would have to be even too for bus 6
+    assert_eq!(None, find_first_contiguous_time(&parse_input_with_offsets("0\n4,6")));
   }
 }
\ No newline at end of file
@@ -38,89 +38,110 @@
   What is the sum of all values left in memory after it completes?
 */
 
-use std::i64;
 use regex::Regex;
 use std::collections::HashMap;
 
-pub fn add_mem(instructions: &Vec<String>) -> i64 {
+/// Values and addresses are 36 bits
+const BITS_36: u64 = (1 << 36) - 1;
+
+/// A docking program bitmask, with each kind of mask bit kept as its own set of bits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mask {
+  /// 0 where the mask has a 0
+  and: u64,
+  /// 1 where the mask has a 1
+  or: u64,
+  /// 1 where the mask has an X
+  floating: u64,
+}
+
+impl Mask {
+  /// Reads a mask written as 36 characters of 0, 1 or X, None if it isn't one
+  pub fn parse(mask: &str) -> Option<Mask> {
+    if mask.len() != 36 {
+      return None;
+    }
+    let mut parsed = Mask { and: BITS_36, or: 0, floating: 0 };
+    for (i, bit) in mask.chars().rev().enumerate() {
+      match bit {
+        '0' => parsed.and &= !(1 << i),
+        '1' => parsed.or |= 1 << i,
+        'X' => parsed.floating |= 1 << i,
+        _ => return None,
+      }
+    }
+    Some(parsed)
+  }
+
+  /// Part 1: 0s and 1s overwrite the value's bits, Xs leave them alone
+  pub fn apply_value(&self, value: u64) -> u64 {
+    (value & self.and) | self.or
+  }
+
+  /// Part 2: 1s set the address's bits, and Xs take on every combination of 0 and 1
+  pub fn addresses(&self, address: u64) -> FloatingAddresses {
+    FloatingAddresses {
+      base: (address | self.or) & !self.floating & BITS_36,
+      floating: self.floating,
+      next: Some(0),
+    }
+  }
+}
+
+/// Every address a floating mask writes to, 2 to the power of the number of Xs of them
+pub struct FloatingAddresses {
+  base: u64,
+  floating: u64,
+  /// The floating bits to set in the next address
+  next: Option<u64>,
+}
+
+impl Iterator for FloatingAddresses {
+  type Item = u64;
+
+  fn next(&mut self) -> Option<u64> {
+    let bits = self.next?;
+    // counts up through the subsets of the floating bits, wrapping back to 0 after the last one
+    let following = bits.wrapping_sub(self.floating) & self.floating;
+    self.next = if following == 0 { None } else { Some(following) };
+    Some(self.base | bits)
+  }
+}
+
+pub fn add_mem(instructions: &[String]) -> i64 {
   let mem_regex = Regex::new(r"mem\[([0-9]+)\]").unwrap();
   let mut mem_map = HashMap::new();
-  let mut current_mask = "XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX";
+  let mut current_mask = Mask::parse("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX").unwrap();
   for instruction in instructions {
     if instruction.contains("mask =") {
-      current_mask = instruction.split("=").last().unwrap().trim();
+      current_mask = Mask::parse(instruction.split("=").last().unwrap().trim()).unwrap();
     } else {
       let mem = mem_regex.captures(instruction).unwrap().get(1).map_or("", |m| m.as_str());
       let val: u64 = instruction.split("=").last().unwrap().trim().parse().unwrap();
-
-      let base_2 = pad_36(&format_radix(val, 2));
-      mem_map.insert(mem, apply_mask(current_mask, &base_2));
+      mem_map.insert(mem, current_mask.apply_value(val));
     }
   }
-  return mem_map.values()
-    .map(|val| i64::from_str_radix(val, 2).unwrap())
-    .sum();
+  mem_map.values().sum::<u64>() as i64
 }
 
-pub fn add_mem_v2(instructions: &Vec<String>) -> u64 {
+pub fn add_mem_v2(instructions: &[String]) -> u64 {
   let mem_regex = Regex::new(r"mem\[([0-9]+)\]").unwrap();
   let mut mem_map = HashMap::new();
-  let mut current_mask = "000000000000000000000000000000000000";
+  let mut current_mask = Mask::parse("000000000000000000000000000000000000").unwrap();
   for instruction in instructions {
     if instruction.contains("mask =") {
-      current_mask = instruction.split("=").last().unwrap().trim();
+      current_mask = Mask::parse(instruction.split("=").last().unwrap().trim()).unwrap();
     } else {
       let mem: u64 = mem_regex.captures(instruction).unwrap()
         .get(1).map_or("", |m| m.as_str())
         .parse().unwrap();
       let val: u64 = instruction.split("=").last().unwrap().trim().parse().unwrap();
-
-      let all_mem = apply_mem_mask(current_mask, &pad_36(&format_radix(mem, 2)));
-      for mem_addr in all_mem {
+      for mem_addr in current_mask.addresses(mem) {
         mem_map.insert(mem_addr, val);
       }
     }
   }
-  return mem_map.values().sum();
-}
-
-fn apply_mem_mask(mask: &str, value: &str) -> Vec<i64> {
-  let mut all_mem = vec![Vec::new()];
-  let mut digits = value.chars();
-  for m in mask.chars() {
-    let digit = digits.next().unwrap();
-    for i in 0..all_mem.len() {
-      let current_mem = &mut all_mem[i];
-      if m == '0' {
-        current_mem.push(digit);
-      } else if m == '1' {
-        current_mem.push('1');
-      } else {
-        let mut new_mem = current_mem.clone();
-        current_mem.push('0');
-        new_mem.push('1');
-        all_mem.push(new_mem);
-      }
-    }
-  }
-  return all_mem.iter()
-    .map(|m| m.iter().collect::<String>())
-    .map(|s| i64::from_str_radix(&s, 2).unwrap())
-    .collect();
-}
-
-fn apply_mask(mask: &str, value: &str) -> String {
-  let mut mask_applied = Vec::new();
-  let mut digits = value.chars();
-  for m in mask.chars() {
-    let digit = digits.next().unwrap();
-    if m == 'X' {
-      mask_applied.push(digit);
-    } else {
-      mask_applied.push(m);
-    }
-  }
-  return mask_applied.iter().collect();
+  mem_map.values().sum()
 }
 
 pub fn parse_input(input: &str) -> Vec<String> {
@@ -131,27 +152,6 @@ pub fn parse_input(input: &str) -> Vec<String> {
   return vec;
 }
 
-// https://stackoverflow.com/a/50278316/1856960
-fn format_radix(mut x: u64, radix: u32) -> String {
-  let mut result = vec![];
-
-  loop {
-    let m = (x % radix as u64) as u32;
-    x = x / radix as u64;
-
-    // will panic if you use a bad radix (< 2 or > 36).
-    result.push(std::char::from_digit(m, radix).unwrap());
-    if x == 0 {
-      break;
-    }
-  }
-  result.into_iter().rev().collect::<String>()
-}
-
-fn pad_36(input: &str) -> String {
-  return format!("{:0>36}", input);
-}
-
 pub fn read_input() -> String {
   return "mask = 000001001011XX1XX100X0001011X0001101
     mem[54977] = 194579
@@ -729,9 +729,11 @@ mod tests {
   use super::*;
 
   #[test]
-  fn radix_convert() {
-    assert_eq!("000000000000000000000000000000001011", pad_36(&format_radix(11, 2)));
-    assert_eq!("000000000000000000000000000001100101", pad_36(&format_radix(101, 2)));
+  fn parse_mask() {
+    let mask = Mask::parse("XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X").unwrap();
+    assert_eq!(Mask { and: BITS_36 & !0b10, or: 0b1000000, floating: BITS_36 & !0b1000010 }, mask);
+    assert_eq!(None, Mask::parse("X1XX0X"));
+    assert_eq!(None, Mask::parse("XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX2X"));
   }
 
   #[test]
@@ -745,10 +747,10 @@ mod tests {
 
   #[test]
   fn apply_36_mask() {
-    let mask = "XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X";
-    assert_eq!("000000000000000000000000000001001001", apply_mask(mask, "000000000000000000000000000000001011"));
-    assert_eq!("000000000000000000000000000001000000", apply_mask(mask, "000000000000000000000000000000000000"));
-    assert_eq!("000000000000000000000000000001100101", apply_mask(mask, "000000000000000000000000000001100101"));
+    let mask = Mask::parse("XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X").unwrap();
+    assert_eq!(73, mask.apply_value(11));
+    assert_eq!(64, mask.apply_value(0));
+    assert_eq!(101, mask.apply_value(101));
   }
 
   #[test]
@@ -768,8 +770,18 @@ mod tests {
 
   #[test]
   fn appply_mask_v2(){
-    let mask = "000000000000000000000000000000X1001X";
-    let expected = vec![26,58,27,59];
-    assert_eq!(expected, apply_mem_mask(mask, "000000000000000000000000000000101010"))
+    let mask = Mask::parse("000000000000000000000000000000X1001X").unwrap();
+    let expected = vec![26,27,58,59];
+    assert_eq!(expected, mask.addresses(42).collect::<Vec<u64>>());
+    let mask = Mask::parse("00000000000000000000000000000000X0XX").unwrap();
+    assert_eq!(vec![16,17,18,19,24,25,26,27], mask.addresses(26).collect::<Vec<u64>>());
+  }
+
+  #[test]
+  fn many_floating_bits() {
+    let mask = Mask::parse("1111111111111111XXXXXXXXXXXXXXXXXXXX").unwrap();
+    assert_eq!(1 << 20, mask.addresses(12345).count());
+    assert_eq!(Some(BITS_36 & !0xfffff), mask.addresses(0).next());
+    assert_eq!(Some(BITS_36), mask.addresses(0).last());
   }
 }
\ No newline at end of file
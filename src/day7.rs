@@ -8,7 +8,9 @@
   Part 2: How many individual bags are required inside your single shiny gold bag?
 */
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use num_bigint::BigUint;
 use regex::Regex;
 
 const NO_BAGS: &str = "no other bags.";
@@ -51,25 +53,145 @@ fn recurse_to_gold(bag: &str, map: &HashMap<String, Vec<Rule>>, memo: &mut Vec<S
   return false;
 }
 
-pub fn count_required_bags(bag_rules: &HashMap<String, Vec<Rule>>) -> i32 {
-  let mut memo = HashMap::new();
+/// The number of bags inside another bag.
+/// Counting starts with checked u64 arithmetic and switches to a big integer if that overflows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BagCount {
+  Small(u64),
+  Big(BigUint),
+}
+
+impl fmt::Display for BagCount {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      BagCount::Small(count) => write!(f, "{}", count),
+      BagCount::Big(count) => write!(f, "{}", count),
+    }
+  }
+}
+
+pub fn count_required_bags(bag_rules: &HashMap<String, Vec<Rule>>) -> BagCount {
   let gold = bag_rules.get(GOLD).unwrap();
-  return count_internal_bags(gold, bag_rules, &mut memo);
+  match count_internal_bags(gold, bag_rules, &mut HashMap::new()) {
+    Some(count) => BagCount::Small(count),
+    None => BagCount::Big(count_internal_bags_big(gold, bag_rules, &mut HashMap::new())),
+  }
 }
 
-pub fn count_internal_bags(rules: &Vec<Rule>, bag_rules: &HashMap<String, Vec<Rule>>, memo: &mut HashMap<String, i32>) -> i32 {
-  let mut count = 0;
+/// Returns None if the count does not fit in a u64
+pub fn count_internal_bags(rules: &[Rule], bag_rules: &HashMap<String, Vec<Rule>>, memo: &mut HashMap<String, u64>) -> Option<u64> {
+  let mut count: u64 = 0;
   for rule in rules {
-    let internal_count;
-    if memo.contains_key(&rule.bag) {
-      internal_count = *memo.get(&rule.bag).unwrap();
-    } else {
-      internal_count = count_internal_bags(bag_rules.get(&rule.bag).unwrap(), bag_rules, memo);
+    let internal_count = match memo.get(&rule.bag) {
+      Some(internal_count) => *internal_count,
+      None => {
+        let internal_count = count_internal_bags(&bag_rules[&rule.bag], bag_rules, memo)?;
+        memo.insert(rule.bag.to_string(), internal_count);
+        internal_count
+      }
+    };
+    // each rule adds the bags themselves plus everything inside of them
+    let with_contents = internal_count.checked_add(1)?.checked_mul(rule.count as u64)?;
+    count = count.checked_add(with_contents)?;
+  }
+  Some(count)
+}
+
+pub fn count_internal_bags_big(rules: &[Rule], bag_rules: &HashMap<String, Vec<Rule>>, memo: &mut HashMap<String, BigUint>) -> BigUint {
+  let mut count = BigUint::from(0u32);
+  for rule in rules {
+    if !memo.contains_key(&rule.bag) {
+      let internal_count = count_internal_bags_big(&bag_rules[&rule.bag], bag_rules, memo);
       memo.insert(rule.bag.to_string(), internal_count);
     }
-    count += rule.count + rule.count * internal_count;
+    count += (&memo[&rule.bag] + 1u32) * BigUint::from(rule.count as u32);
   }
-  return count;
+  count
+}
+
+/// How many bags of each color end up inside the given bag
+pub fn bag_breakdown(bag: &str, bag_rules: &HashMap<String, Vec<Rule>>) -> BTreeMap<String, BigUint> {
+  let mut memo = HashMap::new();
+  breakdown_internal(bag, bag_rules, &mut memo).clone()
+}
+
+fn breakdown_internal<'a>(bag: &str, bag_rules: &HashMap<String, Vec<Rule>>, memo: &'a mut HashMap<String, BTreeMap<String, BigUint>>) -> &'a BTreeMap<String, BigUint> {
+  if !memo.contains_key(bag) {
+    let mut breakdown: BTreeMap<String, BigUint> = BTreeMap::new();
+    for rule in bag_rules.get(bag).into_iter().flatten() {
+      let multiplier = BigUint::from(rule.count as u32);
+      *breakdown.entry(rule.bag.to_string()).or_default() += &multiplier;
+      for (inner_bag, inner_count) in breakdown_internal(&rule.bag, bag_rules, memo) {
+        *breakdown.entry(inner_bag.to_string()).or_default() += inner_count * &multiplier;
+      }
+    }
+    memo.insert(bag.to_string(), breakdown);
+  }
+  &memo[bag]
+}
+
+/// Which part of the rule graph to include in a DOT export
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DotScope<'a> {
+  /// Every bag color and every rule
+  All,
+  /// The chosen bag and every bag that can end up inside of it
+  ContainedIn(&'a str),
+  /// The chosen bag and every bag that can eventually hold it
+  Containing(&'a str),
+}
+
+/// Render the bag rules as a Graphviz DOT digraph.
+/// Each bag color is a node, and each rule is an edge from the outer bag to the inner bag
+/// labeled and weighted by the number of inner bags required.
+pub fn rules_to_dot(bag_rules: &HashMap<String, Vec<Rule>>, scope: DotScope) -> String {
+  let nodes = match scope {
+    DotScope::All => {
+      let mut all: BTreeSet<&str> = bag_rules.keys().map(|bag| bag.as_str()).collect();
+      all.extend(bag_rules.values().flatten().map(|rule| rule.bag.as_str()));
+      all
+    },
+    DotScope::ContainedIn(bag) => reachable(bag, |outer| {
+      bag_rules.get(outer).into_iter().flatten().map(|rule| rule.bag.as_str()).collect()
+    }),
+    DotScope::Containing(bag) => reachable(bag, |inner| {
+      bag_rules.iter()
+        .filter(|(_, rules)| rules.iter().any(|rule| rule.bag == inner))
+        .map(|(outer, _)| outer.as_str())
+        .collect()
+    }),
+  };
+
+  let mut dot = String::from("digraph bags {\n");
+  for node in &nodes {
+    dot.push_str(&format!("  {};\n", dot_id(node)));
+  }
+  for &outer in &nodes {
+    for rule in bag_rules.get(outer).into_iter().flatten() {
+      if nodes.contains(rule.bag.as_str()) {
+        dot.push_str(&format!("  {} -> {} [label=\"{}\", weight={}];\n", dot_id(outer), dot_id(&rule.bag), rule.count, rule.count));
+      }
+    }
+  }
+  dot.push_str("}\n");
+  dot
+}
+
+// Breadth first walk from the starting bag using the supplied neighbor function
+fn reachable<'a, F>(start: &'a str, neighbors: F) -> BTreeSet<&'a str>
+  where F: Fn(&str) -> Vec<&'a str> {
+  let mut seen = BTreeSet::new();
+  let mut queue = vec![start];
+  while let Some(bag) = queue.pop() {
+    if seen.insert(bag) {
+      queue.extend(neighbors(bag));
+    }
+  }
+  seen
+}
+
+fn dot_id(bag: &str) -> String {
+  format!("\"{}\"", bag.replace('"', "\\\""))
 }
 
 fn replace_trailing_s(bag: &str) -> String {
@@ -672,7 +794,10 @@ pub fn read_rules() -> HashMap<String, Vec<Rule>> {
     mirrored orange bags contain 1 plaid magenta bag, 5 muted red bags, 3 pale lime bags.
     faded magenta bags contain 3 striped cyan bags, 4 muted silver bags.
     clear gray bags contain 4 muted gray bags, 2 wavy turquoise bags, 3 dotted plum bags.";
+  parse_rules(raw_rules)
+}
 
+pub fn parse_rules(raw_rules: &str) -> HashMap<String, Vec<Rule>> {
   let mut map: HashMap<String, Vec<Rule>> = HashMap::new(); 
   for rule_line in raw_rules.split("\n") {
     let bag_definition = rule_line.split("contain").collect::<Vec<&str>>();
@@ -710,6 +835,63 @@ mod tests {
   #[test]
   fn count_bags() {
     let bag_rules = read_rules();
-    assert_eq!(172246, count_required_bags(&bag_rules));
+    assert_eq!(BagCount::Small(172246), count_required_bags(&bag_rules));
+  }
+
+  const EXAMPLE_RULES: &str = "light red bags contain 1 bright white bag, 2 muted yellow bags.
+    dark orange bags contain 3 bright white bags, 4 muted yellow bags.
+    bright white bags contain 1 shiny gold bag.
+    muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+    shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.
+    dark olive bags contain 3 faded blue bags, 4 dotted black bags.
+    vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
+    faded blue bags contain no other bags.
+    dotted black bags contain no other bags.";
+
+  #[test]
+  fn count_bags_breakdown() {
+    let breakdown = bag_breakdown(GOLD, &parse_rules(EXAMPLE_RULES));
+    assert_eq!(4, breakdown.len());
+    assert_eq!(BigUint::from(1u32), breakdown["dark olive bag"]);
+    assert_eq!(BigUint::from(2u32), breakdown["vibrant plum bag"]);
+    assert_eq!(BigUint::from(13u32), breakdown["faded blue bag"]);
+    assert_eq!(BigUint::from(16u32), breakdown["dotted black bag"]);
+  }
+
+  #[test]
+  fn count_bags_overflow() {
+    // 25 levels of 9 bags each is far too many bags for a u64
+    let mut rules = String::from("shiny gold bags contain 9 deep n1 bags.");
+    for i in 1..25 {
+      rules.push_str(&format!("\n    deep n{} bags contain 9 deep n{} bags.", i, i + 1));
+    }
+    rules.push_str("\n    deep n25 bags contain no other bags.");
+    let bag_rules = parse_rules(&rules);
+    let expected = (BigUint::from(9u32).pow(26) - 9u32) / 8u32;
+    assert_eq!(BagCount::Big(expected.clone()), count_required_bags(&bag_rules));
+    assert_eq!(expected, bag_breakdown(GOLD, &bag_rules).values().sum());
+  }
+
+  #[test]
+  fn dot_export_all() {
+    let dot = rules_to_dot(&parse_rules(EXAMPLE_RULES), DotScope::All);
+    assert!(dot.starts_with("digraph bags {\n"));
+    assert_eq!(9, dot.lines().filter(|line| line.ends_with("bag\";")).count());
+    assert_eq!(13, dot.lines().filter(|line| line.contains("->")).count());
+    assert!(dot.contains("  \"muted yellow bag\" -> \"faded blue bag\" [label=\"9\", weight=9];\n"));
+  }
+
+  #[test]
+  fn dot_export_scoped() {
+    let bag_rules = parse_rules(EXAMPLE_RULES);
+    let inside = rules_to_dot(&bag_rules, DotScope::ContainedIn(GOLD));
+    assert_eq!(5, inside.lines().filter(|line| line.ends_with("bag\";")).count());
+    assert_eq!(6, inside.lines().filter(|line| line.contains("->")).count());
+    assert!(!inside.contains("light red bag"));
+
+    let outside = rules_to_dot(&bag_rules, DotScope::Containing(GOLD));
+    assert_eq!(5, outside.lines().filter(|line| line.ends_with("bag\";")).count());
+    assert_eq!(6, outside.lines().filter(|line| line.contains("->")).count());
+    assert!(!outside.contains("faded blue bag"));
   }
 }
\ No newline at end of file
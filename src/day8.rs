@@ -14,94 +14,870 @@
   Find the incorrect instruction and change it. What is the value in a accumulator when the program terminates?
 */
 
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::sync::OnceLock;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Insruction {
   Nop,
   Acc,
   Jmp,
+  /// Any other instruction, defined by an InstructionSet
+  Custom(String),
 }
 
 impl Insruction {
+  /// Anything other than the boot code instructions is a custom instruction
   fn from_string(val: &str)-> Insruction {
-    if val == "acc" {
-      return Insruction::Acc;
-    } else if val == "jmp" {
-      return Insruction::Jmp
+    match val {
+      "acc" => Insruction::Acc,
+      "jmp" => Insruction::Jmp,
+      "nop" => Insruction::Nop,
+      _ => Insruction::Custom(val.to_string()),
+    }
+  }
+
+  fn mnemonic(&self) -> &str {
+    match self {
+      Insruction::Acc => "acc",
+      Insruction::Jmp => "jmp",
+      Insruction::Nop => "nop",
+      Insruction::Custom(mnemonic) => mnemonic,
     }
-    return Insruction::Nop;
   }
 }
 
-#[derive(Debug, Clone)]
+impl fmt::Display for Insruction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.mnemonic())
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct CodeLine {
   instruction: Insruction,
+  register: Option<char>,
   value: i32,
-  times_executed: i32,
 }
 
-impl CodeLine {
-  pub fn execute(&mut self) {
-    self.times_executed += 1;
+/// How an instruction can move the program counter, used for control flow analysis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flow {
+  /// Always continues with the next line
+  Next,
+  /// Always jumps by its value
+  Jump,
+  /// Either continues with the next line or jumps by its value
+  Branch,
+}
+
+/// The arguments an instruction is written with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operands {
+  /// A signed number or label, like `acc +3`
+  Value,
+  /// A single letter register, like `out a`
+  Register,
+  /// A register followed by a number or label, like `jnz a -2`
+  RegisterValue,
+}
+
+/// Registers, input and output that instructions can work with
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MachineState {
+  pub accumulator: i32,
+  pub registers: HashMap<char, i32>,
+  pub input: VecDeque<i32>,
+  pub output: Vec<i32>,
+}
+
+impl MachineState {
+  /// Registers start at 0
+  pub fn register(&self, name: char) -> i32 {
+    self.registers.get(&name).copied().unwrap_or(0)
+  }
+
+  pub fn set_register(&mut self, name: char, value: i32) {
+    self.registers.insert(name, value);
+  }
+}
+
+/// What an instruction does. It is given the machine state, the register (for instructions that take one)
+/// and the value, and returns how far to move the program counter.
+/// The register is always given for instructions whose operands include one.
+pub type Semantics = fn(&mut MachineState, Option<char>, i32) -> i32;
+
+#[derive(Debug, Clone)]
+pub struct InstructionDefinition {
+  pub operands: Operands,
+  pub flow: Flow,
+  pub execute: Semantics,
+}
+
+/// The instructions a program can use, looked up by mnemonic
+#[derive(Debug, Clone)]
+pub struct InstructionSet {
+  definitions: HashMap<String, InstructionDefinition>,
+}
+
+impl Default for InstructionSet {
+  /// The boot code instructions: acc, jmp, and nop
+  fn default() -> InstructionSet {
+    let mut instruction_set = InstructionSet { definitions: HashMap::new() };
+    instruction_set.register("acc", Operands::Value, Flow::Next, |state, _, value| {
+      state.accumulator += value;
+      1
+    });
+    instruction_set.register("jmp", Operands::Value, Flow::Jump, |_, _, value| value);
+    instruction_set.register("nop", Operands::Value, Flow::Next, |_, _, _| 1);
+    instruction_set
+  }
+}
+
+impl InstructionSet {
+  /// The boot code instructions plus registers and input/output
+  ///   set r v  register r = v
+  ///   add r v  register r += v
+  ///   acr r    accumulator += register r
+  ///   jnz r v  jump by v if register r is not 0
+  ///   inp r    read the next input into register r (0 if there is no more input)
+  ///   out r    write register r to the output
+  pub fn extended() -> InstructionSet {
+    let mut instruction_set = InstructionSet::default();
+    instruction_set.register("set", Operands::RegisterValue, Flow::Next, |state, register, value| {
+      state.set_register(register.unwrap(), value);
+      1
+    });
+    instruction_set.register("add", Operands::RegisterValue, Flow::Next, |state, register, value| {
+      let register = register.unwrap();
+      state.set_register(register, state.register(register) + value);
+      1
+    });
+    instruction_set.register("acr", Operands::Register, Flow::Next, |state, register, _| {
+      state.accumulator += state.register(register.unwrap());
+      1
+    });
+    instruction_set.register("jnz", Operands::RegisterValue, Flow::Branch, |state, register, value| {
+      if state.register(register.unwrap()) != 0 { value } else { 1 }
+    });
+    instruction_set.register("inp", Operands::Register, Flow::Next, |state, register, _| {
+      let input = state.input.pop_front().unwrap_or(0);
+      state.set_register(register.unwrap(), input);
+      1
+    });
+    instruction_set.register("out", Operands::Register, Flow::Next, |state, register, _| {
+      let output = state.register(register.unwrap());
+      state.output.push(output);
+      1
+    });
+    instruction_set
+  }
+
+  /// Add an instruction, replacing any existing instruction with the same mnemonic
+  pub fn register(&mut self, mnemonic: &str, operands: Operands, flow: Flow, execute: Semantics) {
+    self.definitions.insert(mnemonic.to_string(), InstructionDefinition { operands, flow, execute });
+  }
+
+  pub fn get(&self, instruction: &Insruction) -> Option<&InstructionDefinition> {
+    self.definitions.get(instruction.mnemonic())
+  }
+}
+
+fn boot_code_instructions() -> &'static InstructionSet {
+  static BOOT_CODE: OnceLock<InstructionSet> = OnceLock::new();
+  BOOT_CODE.get_or_init(InstructionSet::default)
+}
+
+/// Why the virtual machine stopped (or didn't) after a step or run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VmStatus {
+  /// The last instruction executed and the machine can keep going
+  Running,
+  /// The program counter moved to the line immediately after the last instruction
+  Terminated,
+  /// The program counter jumped somewhere outside of the program
+  OutOfBounds(i32),
+  /// The halting policy stopped the machine before running the line at the program counter
+  Halted,
+  /// Stopped at a breakpoint before running the line at the program counter
+  Breakpoint,
+  /// The run_until condition was met
+  ConditionMet,
+  /// The line at the program counter uses an instruction missing from the instruction set,
+  /// or has no register for an instruction that needs one
+  UnknownInstruction(usize),
+}
+
+/// Decides if the machine should stop before executing the next instruction
+pub trait HaltPolicy {
+  fn should_halt(&mut self, pc: usize, steps: usize) -> bool;
+}
+
+/// Any closure taking the program counter and number of steps executed so far is a halting policy
+impl<F: FnMut(usize, usize) -> bool> HaltPolicy for F {
+  fn should_halt(&mut self, pc: usize, steps: usize) -> bool {
+    self(pc, steps)
+  }
+}
+
+/// Halt the first time any instruction is about to run a second time
+#[derive(Debug, Default)]
+pub struct HaltOnRepeat {
+  executed: HashSet<usize>,
+}
+
+impl HaltPolicy for HaltOnRepeat {
+  fn should_halt(&mut self, pc: usize, _steps: usize) -> bool {
+    !self.executed.insert(pc)
+  }
+}
+
+/// Never halt; the machine only stops when the program ends or at a breakpoint
+#[derive(Debug)]
+pub struct NeverHalt;
+
+impl HaltPolicy for NeverHalt {
+  fn should_halt(&mut self, _pc: usize, _steps: usize) -> bool {
+    false
   }
 }
 
-pub fn accumulator_at_infinite_loop(code_original: &Vec<CodeLine>) -> (bool, i32) {
-  let mut code = code_original.clone();
-  let mut accumulator = 0;
-  let mut current_line = 0;
-  let mut terminates = true;
-  while current_line < code.len() {
-    let line = &mut code[current_line];
-    line.execute();
-    if line.times_executed > 1 {
-      terminates = false;
-      break;
+/// One executed instruction, with the accumulator after it ran
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+  pub pc: usize,
+  pub instruction: Insruction,
+  pub register: Option<char>,
+  pub value: i32,
+  pub accumulator: i32,
+}
+
+/// A trace as a plain text log, one step per line
+pub fn trace_log(trace: &[TraceStep]) -> String {
+  trace.iter()
+    .map(|step| format!("{:>5}: {}{} {:+} acc {}\n", step.pc, step.instruction, register_text(step.register), step.value, step.accumulator))
+    .collect()
+}
+
+/// A trace as a JSON array of steps
+pub fn trace_json(trace: &[TraceStep]) -> String {
+  let steps = trace.iter()
+    .map(|step| {
+      let register = step.register.map(|register| format!(",\"register\":\"{}\"", register)).unwrap_or_default();
+      format!("{{\"pc\":{},\"instruction\":\"{}\"{},\"value\":{},\"accumulator\":{}}}", step.pc, step.instruction, register, step.value, step.accumulator)
+    })
+    .collect::<Vec<String>>();
+  format!("[{}]", steps.join(","))
+}
+
+fn register_text(register: Option<char>) -> String {
+  register.map(|register| format!(" {}", register)).unwrap_or_default()
+}
+
+/// Handheld console virtual machine.
+/// The program is borrowed, so running many variations of the same program doesn't require copying it.
+/// Individual lines can be patched to try out a different instruction.
+pub struct Vm<'a> {
+  program: &'a [CodeLine],
+  instruction_set: &'a InstructionSet,
+  patches: HashMap<usize, Insruction>,
+  state: MachineState,
+  pc: i32,
+  steps: usize,
+  breakpoints: HashSet<usize>,
+  halt_policy: Box<dyn HaltPolicy + 'a>,
+  trace: Option<Vec<TraceStep>>,
+}
+
+impl<'a> Vm<'a> {
+  /// Creates a machine for boot code that never halts on its own; see set_halt_policy
+  pub fn new(program: &'a [CodeLine]) -> Vm<'a> {
+    Vm::with_instruction_set(program, boot_code_instructions())
+  }
+
+  pub fn with_instruction_set(program: &'a [CodeLine], instruction_set: &'a InstructionSet) -> Vm<'a> {
+    Vm {
+      program,
+      instruction_set,
+      patches: HashMap::new(),
+      state: MachineState::default(),
+      pc: 0,
+      steps: 0,
+      breakpoints: HashSet::new(),
+      halt_policy: Box::new(NeverHalt),
+      trace: None,
     }
-    if line.instruction == Insruction::Acc {
-      accumulator += line.value;
+  }
+
+  /// Start recording every instruction the machine executes
+  pub fn enable_trace(&mut self) {
+    self.trace.get_or_insert_with(Vec::new);
+  }
+
+  /// Everything recorded since tracing was enabled. Tracing continues with an empty log.
+  pub fn take_trace(&mut self) -> Vec<TraceStep> {
+    self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+  }
+
+  pub fn set_halt_policy<P: HaltPolicy + 'a>(&mut self, policy: P) {
+    self.halt_policy = Box::new(policy);
+  }
+
+  /// Run the given instruction on a line in place of the one in the program
+  pub fn patch(&mut self, line: usize, instruction: Insruction) {
+    self.patches.insert(line, instruction);
+  }
+
+  pub fn add_breakpoint(&mut self, line: usize) {
+    self.breakpoints.insert(line);
+  }
+
+  pub fn remove_breakpoint(&mut self, line: usize) {
+    self.breakpoints.remove(&line);
+  }
+
+  pub fn accumulator(&self) -> i32 {
+    self.state.accumulator
+  }
+
+  pub fn state(&self) -> &MachineState {
+    &self.state
+  }
+
+  /// Use to set registers or provide input before running
+  pub fn state_mut(&mut self) -> &mut MachineState {
+    &mut self.state
+  }
+
+  pub fn pc(&self) -> i32 {
+    self.pc
+  }
+
+  pub fn steps(&self) -> usize {
+    self.steps
+  }
+
+  /// The instruction, register, and value at a line, including any patch
+  pub fn fetch(&self, line: usize) -> Option<(&Insruction, Option<char>, i32)> {
+    let code_line = self.program.get(line)?;
+    let instruction = self.patches.get(&line).unwrap_or(&code_line.instruction);
+    Some((instruction, code_line.register, code_line.value))
+  }
+
+  /// Where the program counter is if it is pointing at a line that can't be executed
+  fn stopped_status(&self) -> Option<VmStatus> {
+    if self.pc as usize == self.program.len() && self.pc >= 0 {
+      Some(VmStatus::Terminated)
+    } else if self.pc < 0 || self.pc as usize > self.program.len() {
+      Some(VmStatus::OutOfBounds(self.pc))
+    } else {
+      None
     }
-    let line_change = match line.instruction {
-      Insruction::Acc => 1,
-      Insruction::Nop => 1,
-      Insruction::Jmp => line.value
+  }
+
+  /// Execute a single instruction, ignoring breakpoints
+  pub fn step(&mut self) -> VmStatus {
+    if let Some(status) = self.stopped_status() {
+      return status;
+    }
+    let line = self.pc as usize;
+    if self.halt_policy.should_halt(line, self.steps) {
+      return VmStatus::Halted;
+    }
+    let (instruction, register, value) = self.fetch(line).unwrap();
+    let definition = match self.instruction_set.get(instruction) {
+      // a patch can put a register instruction on a line that was written without one
+      Some(definition) if register.is_some() || definition.operands == Operands::Value => definition,
+      _ => return VmStatus::UnknownInstruction(line),
     };
-    current_line = (current_line as i32 + line_change) as usize;
+    let instruction = instruction.clone();
+    // no program is anywhere near i32::MAX lines long, so a jump that saturates is still out of bounds
+    self.pc = self.pc.saturating_add((definition.execute)(&mut self.state, register, value));
+    self.steps += 1;
+    if let Some(trace) = self.trace.as_mut() {
+      trace.push(TraceStep { pc: line, instruction, register, value, accumulator: self.state.accumulator });
+    }
+    self.stopped_status().unwrap_or(VmStatus::Running)
   }
-  return (terminates, accumulator);
+
+  /// Run until the program ends, the halting policy stops it, or a breakpoint is reached.
+  /// A breakpoint on the current line is skipped, so calling run again resumes from a breakpoint.
+  pub fn run(&mut self) -> VmStatus {
+    self.run_until(|_| false)
+  }
+
+  /// Like run, but also stops as soon as the condition is true after an instruction executes
+  pub fn run_until<F: Fn(&Vm) -> bool>(&mut self, condition: F) -> VmStatus {
+    let mut first_step = true;
+    loop {
+      if !first_step && self.pc >= 0 && self.breakpoints.contains(&(self.pc as usize)) {
+        return VmStatus::Breakpoint;
+      }
+      first_step = false;
+      let status = self.step();
+      if status != VmStatus::Running {
+        return status;
+      }
+      if condition(self) {
+        return VmStatus::ConditionMet;
+      }
+    }
+  }
+}
+
+pub fn accumulator_at_infinite_loop(code: &[CodeLine]) -> (bool, i32) {
+  let mut vm = Vm::new(code);
+  vm.set_halt_policy(HaltOnRepeat::default());
+  let terminates = vm.run() == VmStatus::Terminated;
+  (terminates, vm.accumulator())
+}
+
+pub fn find_termination(code: &[CodeLine]) -> i32 {
+  repair(code, boot_code_instructions()).map(|repair| repair.accumulator).unwrap_or(0)
 }
 
-pub fn find_termination(code_original: &Vec<CodeLine>) -> i32 {
-  let mut has_terminated = false;
-  let mut accumulator = 0;
-  let mut index = 0;
-  while !has_terminated {
-    let mut code = code_original.clone();
-    let line = &code[index];
-    let new_op = match line.instruction {
+/// A single jmp/nop swap that makes a looping program terminate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Repair {
+  pub line: usize,
+  pub original: Insruction,
+  pub replacement: Insruction,
+  pub accumulator: i32,
+}
+
+/// Find the one jmp/nop swap that makes the program terminate.
+/// Working backwards from the end of the program finds every line that can lead to termination.
+/// The fix is a line on the looping path whose swapped instruction jumps to one of those lines, checked by running the patched program.
+/// For boot code the first such line is always the fix, so this takes linear time.
+/// Returns None if the program already terminates or no single swap fixes it.
+/// A line running a second time counts as an infinite loop, even for instructions that loop on purpose like jnz.
+pub fn repair(code: &[CodeLine], instruction_set: &InstructionSet) -> Option<Repair> {
+  let mut unpatched = Vm::with_instruction_set(code, instruction_set);
+  unpatched.set_halt_policy(HaltOnRepeat::default());
+  unpatched.enable_trace();
+  if unpatched.run() == VmStatus::Terminated {
+    return None;
+  }
+  let looping_path = unpatched.take_trace();
+
+  let end = code.len();
+  // instructions missing from the instruction set are assumed to continue to the next line, like ControlFlowGraph::build
+  let next_lines = |line: usize, instruction: &Insruction| -> Vec<usize> {
+    let flow = instruction_set.get(instruction).map(|definition| definition.flow).unwrap_or(Flow::Next);
+    let offsets = match flow {
+      Flow::Next => vec![1],
+      Flow::Jump => vec![code[line].value],
+      Flow::Branch => vec![1, code[line].value],
+    };
+    offsets.into_iter()
+      .map(|offset| line as i64 + offset as i64)
+      .filter(|&target| target >= 0 && target as usize <= end)
+      .map(|target| target as usize)
+      .collect()
+  };
+
+  // reverse edges, including the end of the program as the last node
+  let mut previous_lines: Vec<Vec<usize>> = vec![Vec::new(); end + 1];
+  for (line, code_line) in code.iter().enumerate() {
+    for target in next_lines(line, &code_line.instruction) {
+      previous_lines[target].push(line);
+    }
+  }
+  let mut reaches_end = vec![false; end + 1];
+  let mut queue = vec![end];
+  while let Some(line) = queue.pop() {
+    if !reaches_end[line] {
+      reaches_end[line] = true;
+      queue.extend(&previous_lines[line]);
+    }
+  }
+
+  for step in looping_path {
+    let replacement = match step.instruction {
       Insruction::Jmp => Insruction::Nop,
       Insruction::Nop => Insruction::Jmp,
-      Insruction::Acc => Insruction::Acc,
+      _ => continue,
     };
-    if line.instruction == Insruction::Acc {
-      index += 1;
-      continue;
+    if next_lines(step.pc, &replacement).iter().any(|&target| reaches_end[target]) {
+      let mut vm = Vm::with_instruction_set(code, instruction_set);
+      vm.patch(step.pc, replacement.clone());
+      vm.set_halt_policy(HaltOnRepeat::default());
+      if vm.run() == VmStatus::Terminated {
+        return Some(Repair { line: step.pc, original: step.instruction, replacement, accumulator: vm.accumulator() });
+      }
+    }
+  }
+  None
+}
+
+/// Where control goes after the last instruction of a basic block
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockTarget {
+  Block(usize),
+  Exit,
+  OutOfBounds(i32),
+}
+
+/// A run of lines that always execute together, from start up to but not including end
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+  pub start: usize,
+  pub end: usize,
+  pub successors: Vec<BlockTarget>,
+}
+
+#[derive(Debug)]
+pub struct ControlFlowGraph {
+  pub blocks: Vec<BasicBlock>,
+  /// Whether each block can be reached from the start of the program
+  pub reachable: Vec<bool>,
+  /// Each loop is a set of blocks that can all reach each other
+  pub loops: Vec<Vec<usize>>,
+}
+
+impl ControlFlowGraph {
+  /// Instructions missing from the instruction set are assumed to continue to the next line
+  pub fn build(code: &[CodeLine], instruction_set: &InstructionSet) -> ControlFlowGraph {
+    let end = code.len();
+    // saturating keeps a huge jump out of bounds instead of overflowing, like Vm::step
+    let jump_target = |line: usize| (line as i32).saturating_add(code[line].value);
+    let flow = |line: usize| instruction_set.get(&code[line].instruction)
+      .map(|definition| definition.flow)
+      .unwrap_or(Flow::Next);
+
+    // A new block starts at the beginning, at every jump target, and after every jump or branch
+    let mut leaders = vec![false; end];
+    if end > 0 {
+      leaders[0] = true;
+    }
+    for line in 0..end {
+      if flow(line) != Flow::Next {
+        let target = jump_target(line);
+        if target >= 0 && (target as usize) < end {
+          leaders[target as usize] = true;
+        }
+        if line + 1 < end {
+          leaders[line + 1] = true;
+        }
+      }
     }
-    code[index] = CodeLine {
-      instruction: new_op,
-      value: line.value,
-      times_executed: 0,
+    let starts = (0..end).filter(|&line| leaders[line]).collect::<Vec<usize>>();
+    let mut block_of_line = vec![0; end];
+    for (block, &start) in starts.iter().enumerate() {
+      let block_end = starts.get(block + 1).copied().unwrap_or(end);
+      block_of_line[start..block_end].iter_mut().for_each(|b| *b = block);
+    }
+    let line_target = |target: i32| -> BlockTarget {
+      if target as usize == end && target >= 0 {
+        BlockTarget::Exit
+      } else if target < 0 || target as usize > end {
+        BlockTarget::OutOfBounds(target)
+      } else {
+        BlockTarget::Block(block_of_line[target as usize])
+      }
     };
 
-    let result = accumulator_at_infinite_loop(&code);
-    if result.0 {
-      has_terminated = true;
-      accumulator = result.1;
+    let blocks = starts.iter().enumerate().map(|(block, &start)| {
+      let block_end = starts.get(block + 1).copied().unwrap_or(end);
+      let last = block_end - 1;
+      let successors = match flow(last) {
+        Flow::Next => vec![line_target(block_end as i32)],
+        Flow::Jump => vec![line_target(jump_target(last))],
+        Flow::Branch => vec![line_target(block_end as i32), line_target(jump_target(last))],
+      };
+      BasicBlock { start, end: block_end, successors }
+    }).collect::<Vec<BasicBlock>>();
+
+    let mut reachable = vec![false; blocks.len()];
+    let mut queue = if blocks.is_empty() { vec![] } else { vec![0] };
+    while let Some(block) = queue.pop() {
+      if !reachable[block] {
+        reachable[block] = true;
+        for successor in &blocks[block].successors {
+          if let BlockTarget::Block(next) = successor {
+            queue.push(*next);
+          }
+        }
+      }
+    }
+
+    let loops = find_loops(&blocks);
+    ControlFlowGraph { blocks, reachable, loops }
+  }
+
+  /// Lines that can never run no matter what happens
+  pub fn unreachable_lines(&self) -> Vec<usize> {
+    self.blocks.iter()
+      .zip(&self.reachable)
+      .filter(|(_, reachable)| !**reachable)
+      .flat_map(|(block, _)| block.start..block.end)
+      .collect()
+  }
+
+  /// Render the graph as a Graphviz DOT digraph. Unreachable blocks are dashed and blocks in a loop are red.
+  pub fn to_dot(&self, code: &[CodeLine]) -> String {
+    let in_loop = self.loops.iter().flatten().collect::<HashSet<&usize>>();
+    let mut dot = String::from("digraph boot_code {\n  node [shape=box, fontname=monospace];\n");
+    for (index, block) in self.blocks.iter().enumerate() {
+      let label = (block.start..block.end)
+        .map(|line| format!("{}: {}{} {:+}\\l", line, code[line].instruction, register_text(code[line].register), code[line].value))
+        .collect::<String>();
+      let mut style = String::new();
+      if !self.reachable[index] {
+        style.push_str(", style=dashed");
+      }
+      if in_loop.contains(&index) {
+        style.push_str(", color=red");
+      }
+      dot.push_str(&format!("  b{} [label=\"{}\"{}];\n", index, label, style));
+    }
+    dot.push_str("  exit [shape=doublecircle];\n");
+    for (index, block) in self.blocks.iter().enumerate() {
+      for successor in &block.successors {
+        match successor {
+          BlockTarget::Block(next) => dot.push_str(&format!("  b{} -> b{};\n", index, next)),
+          BlockTarget::Exit => dot.push_str(&format!("  b{} -> exit;\n", index)),
+          BlockTarget::OutOfBounds(line) => {
+            dot.push_str(&format!("  oob{} [label=\"line {}\", shape=octagon];\n", index, line));
+            dot.push_str(&format!("  b{} -> oob{};\n", index, index));
+          },
+        }
+      }
+    }
+    dot.push_str("}\n");
+    dot
+  }
+}
+
+// Tarjan's strongly connected components, keeping the components that contain a cycle
+fn find_loops(blocks: &[BasicBlock]) -> Vec<Vec<usize>> {
+  struct Tarjan<'b> {
+    blocks: &'b [BasicBlock],
+    index: Vec<Option<usize>>,
+    low_link: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    loops: Vec<Vec<usize>>,
+  }
+
+  impl<'b> Tarjan<'b> {
+    fn visit(&mut self, block: usize) {
+      self.index[block] = Some(self.next_index);
+      self.low_link[block] = self.next_index;
+      self.next_index += 1;
+      self.stack.push(block);
+      self.on_stack[block] = true;
+      for successor in &self.blocks[block].successors {
+        if let BlockTarget::Block(next) = *successor {
+          match self.index[next] {
+            None => {
+              self.visit(next);
+              self.low_link[block] = self.low_link[block].min(self.low_link[next]);
+            },
+            Some(next_index) if self.on_stack[next] => {
+              self.low_link[block] = self.low_link[block].min(next_index);
+            },
+            _ => {},
+          }
+        }
+      }
+      if Some(self.low_link[block]) == self.index[block] {
+        let mut component = Vec::new();
+        while let Some(member) = self.stack.pop() {
+          self.on_stack[member] = false;
+          component.push(member);
+          if member == block {
+            break;
+          }
+        }
+        let self_loop = self.blocks[block].successors.contains(&BlockTarget::Block(block));
+        if component.len() > 1 || self_loop {
+          component.sort_unstable();
+          self.loops.push(component);
+        }
+      }
+    }
+  }
+
+  let mut tarjan = Tarjan {
+    blocks,
+    index: vec![None; blocks.len()],
+    low_link: vec![0; blocks.len()],
+    on_stack: vec![false; blocks.len()],
+    stack: Vec::new(),
+    next_index: 0,
+    loops: Vec::new(),
+  };
+  for block in 0..blocks.len() {
+    if tarjan.index[block].is_none() {
+      tarjan.visit(block);
+    }
+  }
+  let mut loops = tarjan.loops;
+  loops.sort();
+  loops
+}
+
+/// Interactive debugger for a boot code program, reading commands from stdin.
+///   s        step one instruction
+///   c        continue until a breakpoint or the program stops
+///   b <line> add a breakpoint
+///   d <line> remove a breakpoint
+///   l <max>  stop once max steps have run (0 goes back to stopping when a line repeats)
+///   q        quit
+pub fn debug(code: &[CodeLine]) {
+  let mut vm = Vm::new(code);
+  vm.set_halt_policy(HaltOnRepeat::default());
+  print_vm_state(&vm);
+  for command in io::stdin().lock().lines() {
+    let command = command.unwrap();
+    let parts = command.split_whitespace().collect::<Vec<&str>>();
+    let line_arg = parts.get(1).and_then(|arg| arg.parse::<usize>().ok());
+    match (parts.first().copied(), line_arg) {
+      (Some("s"), _) => println!("{:?}", vm.step()),
+      (Some("c"), _) => println!("{:?}", vm.run()),
+      (Some("b"), Some(line)) => vm.add_breakpoint(line),
+      (Some("d"), Some(line)) => vm.remove_breakpoint(line),
+      (Some("l"), Some(0)) => vm.set_halt_policy(HaltOnRepeat::default()),
+      (Some("l"), Some(max)) => vm.set_halt_policy(move |_, steps| steps >= max),
+      (Some("q"), _) => break,
+      _ => println!("commands: s, c, b <line>, d <line>, l <max>, q"),
+    }
+    print_vm_state(&vm);
+  }
+}
+
+fn print_vm_state(vm: &Vm) {
+  // a jump before line 0 leaves a negative pc, which isn't the end of the program
+  let next = if vm.pc() < 0 {
+    String::from("(out of bounds)")
+  } else {
+    vm.fetch(vm.pc() as usize)
+      .map(|(instruction, register, value)| format!("{}{} {:+}", instruction, register_text(register), value))
+      .unwrap_or_else(|| String::from("(end)"))
+  };
+  print!("pc {} acc {} steps {} next {} > ", vm.pc(), vm.accumulator(), vm.steps(), next);
+  io::stdout().flush().unwrap();
+}
+
+/// Problems found while assembling boot code. Line numbers start at 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssembleError {
+  UnknownMnemonic { line: usize, mnemonic: String },
+  MissingArgument { line: usize },
+  InvalidArgument { line: usize, argument: String },
+  UnknownLabel { line: usize, label: String },
+  DuplicateLabel { line: usize, label: String },
+}
+
+impl fmt::Display for AssembleError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      AssembleError::UnknownMnemonic { line, mnemonic } => write!(f, "line {}: unknown instruction '{}'", line, mnemonic),
+      AssembleError::MissingArgument { line } => write!(f, "line {}: missing argument", line),
+      AssembleError::InvalidArgument { line, argument } => write!(f, "line {}: invalid argument '{}'", line, argument),
+      AssembleError::UnknownLabel { line, label } => write!(f, "line {}: unknown label '{}'", line, label),
+      AssembleError::DuplicateLabel { line, label } => write!(f, "line {}: label '{}' is already defined", line, label),
+    }
+  }
+}
+
+/// Assemble source into a program using the mnemonics and operands from an instruction set.
+///   - Each line holds at most one instruction: a mnemonic followed by its operands
+///   - A register is a single lower case letter. A value is a signed number or a label
+///   - A label is a name followed by a colon, either on its own line or in front of an instruction
+///   - A label argument is replaced by the offset from the instruction to the labeled line
+///   - Everything after a # or ; is a comment, and blank lines are ignored
+pub fn assemble(source: &str, instruction_set: &InstructionSet) -> Result<Vec<CodeLine>, AssembleError> {
+  // First pass finds the instruction index of every label
+  let mut labels: HashMap<&str, usize> = HashMap::new();
+  let mut statements: Vec<(usize, &str, Vec<&str>)> = Vec::new();
+  for (line_index, line) in source.lines().enumerate() {
+    let line_number = line_index + 1;
+    let mut code = line.split(['#', ';']).next().unwrap().trim();
+    if let Some(colon) = code.find(':') {
+      let label = code[..colon].trim();
+      if !is_label(label) {
+        return Err(AssembleError::InvalidArgument { line: line_number, argument: label.to_string() });
+      }
+      if labels.insert(label, statements.len()).is_some() {
+        return Err(AssembleError::DuplicateLabel { line: line_number, label: label.to_string() });
+      }
+      code = code[colon + 1..].trim();
+    }
+    let mut parts = code.split_whitespace();
+    if let Some(mnemonic) = parts.next() {
+      statements.push((line_number, mnemonic, parts.collect()));
+    }
+  }
+
+  // Second pass resolves instructions and arguments
+  let mut program = Vec::new();
+  for (index, (line, mnemonic, arguments)) in statements.into_iter().enumerate() {
+    let instruction = Insruction::from_string(mnemonic);
+    let definition = instruction_set.get(&instruction)
+      .ok_or_else(|| AssembleError::UnknownMnemonic { line, mnemonic: mnemonic.to_string() })?;
+    let expected = if definition.operands == Operands::RegisterValue { 2 } else { 1 };
+    if arguments.len() < expected {
+      return Err(AssembleError::MissingArgument { line });
+    }
+    if let Some(extra) = arguments.get(expected) {
+      return Err(AssembleError::InvalidArgument { line, argument: extra.to_string() });
     }
-    index += 1;
+
+    let register = match definition.operands {
+      Operands::Value => None,
+      Operands::Register | Operands::RegisterValue => {
+        let mut chars = arguments[0].chars();
+        match (chars.next(), chars.next()) {
+          (Some(register), None) if register.is_ascii_lowercase() => Some(register),
+          _ => return Err(AssembleError::InvalidArgument { line, argument: arguments[0].to_string() }),
+        }
+      },
+    };
+    let value = match definition.operands {
+      Operands::Register => 0,
+      Operands::Value | Operands::RegisterValue => {
+        let argument = arguments[expected - 1];
+        match argument.parse::<i32>() {
+          Ok(value) => value,
+          Err(_) if is_label(argument) => {
+            let target = labels.get(argument)
+              .ok_or_else(|| AssembleError::UnknownLabel { line, label: argument.to_string() })?;
+            *target as i32 - index as i32
+          },
+          Err(_) => return Err(AssembleError::InvalidArgument { line, argument: argument.to_string() }),
+        }
+      },
+    };
+    program.push(CodeLine { instruction, register, value });
   }
-  return accumulator;
+  Ok(program)
 }
 
+fn is_label(name: &str) -> bool {
+  let mut chars = name.chars();
+  match chars.next() {
+    Some(first) => (first.is_ascii_alphabetic() || first == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+    None => false,
+  }
+}
+
+/// Write a program as canonical source: one instruction per line, with a signed value for instructions that take one
+pub fn disassemble(code: &[CodeLine], instruction_set: &InstructionSet) -> String {
+  code.iter()
+    .map(|line| {
+      let takes_value = instruction_set.get(&line.instruction)
+        .map(|definition| definition.operands != Operands::Register)
+        .unwrap_or(true);
+      let value = if takes_value { format!(" {:+}", line.value) } else { String::new() };
+      format!("{}{}{}\n", line.instruction, register_text(line.register), value)
+    })
+    .collect()
+}
 
 pub fn read_boot_instructions() -> Vec<CodeLine> {
   let boot_code = "acc -7
@@ -728,17 +1504,210 @@ pub fn read_boot_instructions() -> Vec<CodeLine> {
     acc -13
     acc +34
     jmp +1";
+  parse_boot_instructions(boot_code)
+}
+
+pub fn parse_boot_instructions(boot_code: &str) -> Vec<CodeLine> {
+  assemble(boot_code, boot_code_instructions()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const EXAMPLE: &str = "nop +0
+    acc +1
+    jmp +4
+    acc +3
+    jmp -3
+    acc -99
+    acc +1
+    jmp -4
+    acc +6";
+
+  #[test]
+  fn example_infinite_loop() {
+    assert_eq!((false, 5), accumulator_at_infinite_loop(&parse_boot_instructions(EXAMPLE)));
+  }
 
-  let mut instructions = Vec::new();
-  for line in boot_code.split("\n") {
-    let line_split = line.trim().split_whitespace().collect::<Vec<&str>>();
-    let value = line_split[1].replace("+", "").parse().unwrap();
-    instructions.push(CodeLine{
-      instruction: Insruction::from_string(line_split[0]),
-      value,
-      times_executed: 0
+  #[test]
+  fn example_termination() {
+    assert_eq!(8, find_termination(&parse_boot_instructions(EXAMPLE)));
+  }
+
+  fn repair_boot_code_example(boot_code: &str) -> Option<Repair> {
+    repair(&parse_boot_instructions(boot_code), &InstructionSet::default())
+  }
+
+  #[test]
+  fn example_repair() {
+    let expected = Repair { line: 7, original: Insruction::Jmp, replacement: Insruction::Nop, accumulator: 8 };
+    assert_eq!(Some(expected), repair_boot_code_example(EXAMPLE));
+    assert_eq!(None, repair_boot_code_example("acc +1\nnop +5"));
+    // already terminates, even though swapping the nop would still reach the end
+    assert_eq!(None, repair_boot_code_example("nop +0\nacc +1"));
+  }
+
+  #[test]
+  fn repair_extended() {
+    let extended = InstructionSet::extended();
+    let code = assemble("set a +5\nacr a\njmp -1\nacc +1", &extended).unwrap();
+    let expected = Repair { line: 2, original: Insruction::Jmp, replacement: Insruction::Nop, accumulator: 6 };
+    assert_eq!(Some(expected), repair(&code, &extended));
+    // jnz could continue to the end, but with a set it always jumps back
+    let code = assemble("set a +1\njmp +0\njnz a -1", &extended).unwrap();
+    assert_eq!(None, repair(&code, &extended));
+  }
+
+  #[test]
+  fn repair_boot_code() {
+    let code = read_boot_instructions();
+    let fixed = repair(&code, &InstructionSet::default()).unwrap();
+    let mut vm = Vm::new(&code);
+    vm.patch(fixed.line, fixed.replacement.clone());
+    vm.set_halt_policy(HaltOnRepeat::default());
+    assert_eq!(VmStatus::Terminated, vm.run());
+    assert_eq!(1000, fixed.accumulator);
+  }
+
+  #[test]
+  fn trace_example() {
+    let code = parse_boot_instructions(EXAMPLE);
+    let mut vm = Vm::new(&code);
+    vm.set_halt_policy(HaltOnRepeat::default());
+    vm.enable_trace();
+    vm.run();
+    let trace = vm.take_trace();
+    assert_eq!(vec![0, 1, 2, 6, 7, 3, 4], trace.iter().map(|step| step.pc).collect::<Vec<usize>>());
+    assert_eq!(TraceStep { pc: 3, instruction: Insruction::Acc, register: None, value: 3, accumulator: 5 }, trace[5]);
+    assert_eq!("    0: nop +0 acc 0\n    1: acc +1 acc 1\n", trace_log(&trace[..2]));
+    assert_eq!("[{\"pc\":2,\"instruction\":\"jmp\",\"value\":4,\"accumulator\":1}]", trace_json(&trace[2..3]));
+    assert!(vm.take_trace().is_empty());
+  }
+
+  #[test]
+  fn control_flow_graph() {
+    let code = parse_boot_instructions(EXAMPLE);
+    let cfg = ControlFlowGraph::build(&code, &InstructionSet::default());
+    let ranges = cfg.blocks.iter().map(|block| (block.start, block.end)).collect::<Vec<(usize, usize)>>();
+    assert_eq!(vec![(0, 1), (1, 3), (3, 5), (5, 6), (6, 8), (8, 9)], ranges);
+    assert_eq!(vec![BlockTarget::Block(4)], cfg.blocks[1].successors);
+    assert_eq!(vec![BlockTarget::Exit], cfg.blocks[5].successors);
+    assert_eq!(vec![vec![1, 2, 4]], cfg.loops);
+    assert_eq!(vec![5, 8], cfg.unreachable_lines());
+    let dot = cfg.to_dot(&code);
+    assert!(dot.contains("  b3 [label=\"5: acc -99\\l\", style=dashed];\n"));
+    assert!(dot.contains("  b4 -> b2;\n"));
+  }
+
+  #[test]
+  fn assemble_labels_and_comments() {
+    let source = "start: nop +0   # does nothing
+      acc +1
+      jmp end ; skip ahead
+
+      back:
+      acc +3
+      jmp -3
+      acc -99
+      end: acc +1
+      jmp back
+      acc +6";
+    let code = parse_boot_instructions(EXAMPLE);
+    let boot_code = InstructionSet::default();
+    assert_eq!(Ok(code.clone()), assemble(source, &boot_code));
+    assert_eq!(Ok(code.clone()), assemble(&disassemble(&code, &boot_code), &boot_code));
+    assert!(disassemble(&code, &boot_code).starts_with("nop +0\nacc +1\njmp +4\n"));
+  }
+
+  #[test]
+  fn assemble_errors() {
+    let boot_code = InstructionSet::default();
+    assert_eq!(Err(AssembleError::UnknownMnemonic { line: 2, mnemonic: String::from("mul") }), assemble("acc +1\nmul +2", &boot_code));
+    assert_eq!(Err(AssembleError::MissingArgument { line: 1 }), assemble("jmp", &boot_code));
+    assert_eq!(Err(AssembleError::InvalidArgument { line: 1, argument: String::from("+x2") }), assemble("acc +x2", &boot_code));
+    assert_eq!(Err(AssembleError::UnknownLabel { line: 3, label: String::from("nowhere") }), assemble("nop +0\n\njmp nowhere", &boot_code));
+    assert_eq!(Err(AssembleError::DuplicateLabel { line: 2, label: String::from("a") }), assemble("a: nop +0\na: nop +0", &boot_code));
+    let extended = InstructionSet::extended();
+    assert_eq!(Err(AssembleError::MissingArgument { line: 1 }), assemble("jnz a", &extended));
+    assert_eq!(Err(AssembleError::InvalidArgument { line: 1, argument: String::from("A") }), assemble("out A", &extended));
+    assert_eq!(Err(AssembleError::InvalidArgument { line: 1, argument: String::from("+1") }), assemble("out a +1", &extended));
+  }
+
+  #[test]
+  fn extended_instructions() {
+    let extended = InstructionSet::extended();
+    let source = "inp a
+      loop: acr a
+      add a -1
+      jnz a loop
+      set b +7
+      out b";
+    let code = assemble(source, &extended).unwrap();
+    assert_eq!("inp a\nacr a\nadd a -1\njnz a -2\nset b +7\nout b\n", disassemble(&code, &extended));
+
+    let mut vm = Vm::with_instruction_set(&code, &extended);
+    vm.state_mut().input.push_back(4);
+    assert_eq!(VmStatus::Terminated, vm.run());
+    assert_eq!(10, vm.accumulator());
+    assert_eq!(vec![7], vm.state().output);
+
+    let cfg = ControlFlowGraph::build(&code, &extended);
+    assert_eq!(vec![BlockTarget::Block(2), BlockTarget::Block(1)], cfg.blocks[1].successors);
+    assert_eq!(vec![vec![1]], cfg.loops);
+
+    // boot code machines don't know the extended instructions
+    let mut vm = Vm::new(&code);
+    assert_eq!(VmStatus::UnknownInstruction(0), vm.run());
+
+    // patching in an instruction that needs a register, on a line without one
+    let code = assemble("nop +0\nacc +1", &extended).unwrap();
+    let mut vm = Vm::with_instruction_set(&code, &extended);
+    vm.patch(1, Insruction::Custom(String::from("acr")));
+    assert_eq!(VmStatus::UnknownInstruction(1), vm.run());
+  }
+
+  #[test]
+  fn register_new_instruction() {
+    let mut instruction_set = InstructionSet::default();
+    instruction_set.register("mul", Operands::Value, Flow::Next, |state, _, value| {
+      state.accumulator *= value;
+      1
     });
+    let code = assemble("acc +3\nmul -4", &instruction_set).unwrap();
+    let mut vm = Vm::with_instruction_set(&code, &instruction_set);
+    assert_eq!(VmStatus::Terminated, vm.run());
+    assert_eq!(-12, vm.accumulator());
+  }
+
+  #[test]
+  fn extreme_jumps() {
+    let code = parse_boot_instructions("nop +0\njmp +2147483647");
+    let mut vm = Vm::new(&code);
+    assert_eq!(VmStatus::OutOfBounds(i32::MAX), vm.run());
+    let cfg = ControlFlowGraph::build(&code, &InstructionSet::default());
+    assert_eq!(vec![BlockTarget::OutOfBounds(i32::MAX)], cfg.blocks[0].successors);
+
+    let code = parse_boot_instructions("nop +0\njmp -2147483648");
+    assert_eq!(VmStatus::OutOfBounds(-2147483647), Vm::new(&code).run());
   }
-  return instructions;
 
+  #[test]
+  fn vm_breakpoints_and_policy() {
+    let code = parse_boot_instructions(EXAMPLE);
+    let mut vm = Vm::new(&code);
+    vm.add_breakpoint(4);
+    assert_eq!(VmStatus::Breakpoint, vm.run());
+    assert_eq!((4, 5), (vm.pc(), vm.accumulator()));
+    vm.remove_breakpoint(4);
+    vm.set_halt_policy(|_, steps| steps >= 10);
+    assert_eq!(VmStatus::Halted, vm.run());
+    assert_eq!(10, vm.steps());
+
+    let mut vm = Vm::new(&code);
+    vm.patch(7, Insruction::Nop);
+    assert_eq!(VmStatus::ConditionMet, vm.run_until(|vm| vm.accumulator() > 1));
+    assert_eq!(VmStatus::Terminated, vm.run());
+    assert_eq!(8, vm.accumulator());
+  }
 }
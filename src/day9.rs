@@ -7,52 +7,170 @@
 
   Part 2: Find a contiguous set of at least two numbers which sum to the invalid number from part 1.
 */
-pub fn find_first_not_sum(cypher: &Vec<i64>, preamble_length: usize) -> i64 {
-  for current_index in preamble_length..cypher.len() {
-    let current_val = cypher[current_index];
-    let previous_range = current_index - preamble_length;
-    if !check_value(current_val, &cypher[previous_range..current_index]) {
-      return current_val;
-    }
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{self, BufRead};
+use std::ops::Range;
+
+pub fn find_first_not_sum(cypher: &[i64], preamble_length: usize) -> i64 {
+  validate_stream(cypher.iter().copied(), preamble_length)
+    .find_map(|event| match event {
+      XmasEvent::Invalid { value, .. } => Some(value),
+      _ => None,
+    })
+    .unwrap_or(0)
+}
+
+/// Every number after the preamble that is not the sum of two of the previous numbers, with its index
+pub fn find_all_not_sum(cypher: &[i64], preamble_length: usize) -> Vec<(usize, i64)> {
+  validate_stream(cypher.iter().copied(), preamble_length)
+    .filter_map(|event| match event {
+      XmasEvent::Invalid { index, value } => Some((index, value)),
+      _ => None,
+    })
+    .collect()
+}
+
+/// What the validator found for each number in the stream
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum XmasEvent {
+  /// Part of the preamble, so there is nothing to check yet
+  Preamble { index: usize, value: i64 },
+  Valid { index: usize, value: i64 },
+  Invalid { index: usize, value: i64 },
+}
+
+/// Iterator adapter that checks each number as it arrives, so the whole cypher never needs to be in memory
+pub struct XmasValidator<I> {
+  numbers: I,
+  window: XmasWindow,
+  index: usize,
+}
+
+impl<I: Iterator<Item = i64>> Iterator for XmasValidator<I> {
+  type Item = XmasEvent;
+
+  fn next(&mut self) -> Option<XmasEvent> {
+    let value = self.numbers.next()?;
+    let index = self.index;
+    let event = if !self.window.is_full() {
+      XmasEvent::Preamble { index, value }
+    } else if self.window.is_valid(value) {
+      XmasEvent::Valid { index, value }
+    } else {
+      XmasEvent::Invalid { index, value }
+    };
+    self.window.push(value);
+    self.index += 1;
+    Some(event)
   }
-  return 0;
 }
 
-// Should probably memoize this
-fn check_value(value: i64, previous: &[i64]) -> bool {
-  let mut i = 0;
-  while i < previous.len() {
-    let mut j = i +1;
-    while j < previous.len() {
-      if previous[i] + previous[j] == value {
-        return true;
-      }
-      j += 1;
+pub fn validate_stream<I: IntoIterator<Item = i64>>(numbers: I, preamble_length: usize) -> XmasValidator<I::IntoIter> {
+  XmasValidator { numbers: numbers.into_iter(), window: XmasWindow::new(preamble_length), index: 0 }
+}
+
+/// Why a number couldn't be read from the cypher input. Line numbers start at 1.
+#[derive(Debug)]
+pub enum ReadError {
+  Io(io::Error),
+  NotANumber { line: usize, text: String },
+}
+
+impl fmt::Display for ReadError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ReadError::Io(e) => write!(f, "could not read cypher input: {}", e),
+      ReadError::NotANumber { line, text } => write!(f, "line {}: '{}' is not a number", line, text),
     }
-    i += 1;
   }
-  return false;
 }
 
-pub fn find_contiguous_sum(value: i64, cypher: &Vec<i64>) -> i64 {
-  let mut i = 0;
-  while i < cypher.len() - 1 {
-    let mut j = i + 2;
-    let mut sum: i64 = cypher[i..j].iter().sum();
-    while sum <= value {
-      if sum == value {
-        let mut slice = cypher[i..j].iter().collect::<Vec<&i64>>();
-        slice.sort();
-        return slice[0] + slice[slice.len() -1];
+/// Numbers from a file, stdin, or any other reader, one per line. Blank lines are skipped.
+/// Errors are passed on so the caller can decide whether the rest of the stream can be trusted.
+pub fn numbers_from_reader<R: BufRead>(reader: R) -> impl Iterator<Item = Result<i64, ReadError>> {
+  reader.lines()
+    .enumerate()
+    .filter(|(_, line)| line.as_ref().map(|line| !line.trim().is_empty()).unwrap_or(true))
+    .map(|(index, line)| {
+      let line = line.map_err(ReadError::Io)?;
+      let text = line.trim();
+      text.parse().map_err(|_| ReadError::NotANumber { line: index + 1, text: text.to_string() })
+    })
+}
+
+/// The most recent numbers in the cypher, with a count of each value.
+/// Checking a number only needs one lookup per value in the window instead of checking every pair.
+pub struct XmasWindow {
+  size: usize,
+  values: VecDeque<i64>,
+  counts: HashMap<i64, usize>,
+}
+
+impl XmasWindow {
+  pub fn new(size: usize) -> XmasWindow {
+    XmasWindow { size, values: VecDeque::with_capacity(size + 1), counts: HashMap::new() }
+  }
+
+  pub fn is_full(&self) -> bool {
+    self.values.len() == self.size
+  }
+
+  /// Add a number to the window, dropping the oldest number once the window is full
+  pub fn push(&mut self, value: i64) {
+    self.values.push_back(value);
+    *self.counts.entry(value).or_insert(0) += 1;
+    if self.values.len() > self.size {
+      let oldest = self.values.pop_front().unwrap();
+      let count = self.counts.get_mut(&oldest).unwrap();
+      *count -= 1;
+      if *count == 0 {
+        self.counts.remove(&oldest);
       }
-      j += 1;
-      sum = cypher[i..j].iter().sum();
     }
-    i += 1;
   }
-  return 0;
+
+  /// Is the value the sum of two numbers in the window
+  pub fn is_valid(&self, value: i64) -> bool {
+    self.counts.keys().any(|&first| {
+      let second = value - first;
+      match self.counts.get(&second) {
+        // the same value can be used twice if it is in the window twice
+        Some(&count) => second != first || count > 1,
+        None => false,
+      }
+    })
+  }
+}
+
+/// The smallest plus the largest number in the first contiguous run of at least two numbers that add up to value
+pub fn find_contiguous_sum(value: i64, cypher: &[i64]) -> Option<i64> {
+  let range = contiguous_sums(cypher, value, 2).next()?;
+  let run = &cypher[range];
+  Some(run.iter().min()? + run.iter().max()?)
 }
 
+/// Every contiguous run of at least min_length numbers that adds up to target, as index ranges.
+/// Runs are found in order of where they end, using prefix sums: a run from start to end adds up to target
+/// when prefix[end] - prefix[start] == target. Works with negative numbers too.
+pub fn contiguous_sums(cypher: &[i64], target: i64, min_length: usize) -> impl Iterator<Item = Range<usize>> + '_ {
+  let min_length = min_length.max(1);
+  let mut prefix = vec![0; cypher.len() + 1];
+  for (index, value) in cypher.iter().enumerate() {
+    prefix[index + 1] = prefix[index] + value;
+  }
+  // prefix sums that are far enough behind the current end to start a run, mapped to their indexes
+  let mut starts: HashMap<i64, Vec<usize>> = HashMap::new();
+  (min_length..=cypher.len()).flat_map(move |end| {
+    starts.entry(prefix[end - min_length]).or_default().push(end - min_length);
+    starts.get(&(prefix[end] - target))
+      .cloned()
+      .unwrap_or_default()
+      .into_iter()
+      .map(move |start| start..end)
+  })
+}
 
 pub fn read_cypher() -> Vec<i64> {
   let input = "33
@@ -1075,13 +1193,59 @@ mod tests {
 
   #[test]
   fn test_check_val() {
-    let test_previous = [35, 20, 15, 25, 47];
-    assert_eq!(true, check_value(40, &test_previous));
+    let mut window = XmasWindow::new(5);
+    for value in [35, 20, 15, 25, 47] {
+      window.push(value);
+    }
+    assert!(window.is_valid(40));
+    assert!(!window.is_valid(30));
+    window.push(35);
+    assert!(window.is_valid(55));
+    assert!(!window.is_valid(70));
+  }
+
+  #[test]
+  fn all_invalid_numbers() {
+    let test_vec = vec![35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309, 576];
+    assert_eq!(vec![(14, 127)], find_all_not_sum(&test_vec, 5));
+    assert_eq!(vec![(2, 5), (3, 3), (5, 8)], find_all_not_sum(&[1, 2, 5, 3, 8, 8], 2));
+    assert!(find_all_not_sum(&[4, 4, 8], 2).is_empty());
+  }
+
+  #[test]
+  fn stream_events() {
+    let input = "1\n2\n\n3\n10\n 13 \n";
+    let numbers = numbers_from_reader(input.as_bytes()).map(Result::unwrap);
+    let events = validate_stream(numbers, 2).collect::<Vec<XmasEvent>>();
+    assert_eq!(vec![
+      XmasEvent::Preamble { index: 0, value: 1 },
+      XmasEvent::Preamble { index: 1, value: 2 },
+      XmasEvent::Valid { index: 2, value: 3 },
+      XmasEvent::Invalid { index: 3, value: 10 },
+      XmasEvent::Valid { index: 4, value: 13 },
+    ], events);
+  }
+
+  #[test]
+  fn stream_errors() {
+    let numbers = numbers_from_reader("1\n\nx2\n3".as_bytes()).collect::<Vec<Result<i64, ReadError>>>();
+    assert_eq!(3, numbers.len());
+    assert!(matches!(numbers[1], Err(ReadError::NotANumber { line: 3, ref text }) if text == "x2"));
+    assert_eq!(3, *numbers[2].as_ref().unwrap());
   }
 
   #[test]
   fn test_contiguous_values() {
     let test_vec = vec![35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309, 576];
-    assert_eq!(62, find_contiguous_sum(127, &test_vec));
+    assert_eq!(Some(62), find_contiguous_sum(127, &test_vec));
+    assert_eq!(None, find_contiguous_sum(1, &test_vec));
+  }
+
+  #[test]
+  fn test_contiguous_ranges() {
+    let test_vec = vec![35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309, 576];
+    assert_eq!(vec![2..6, 14..15], contiguous_sums(&test_vec, 127, 1).collect::<Vec<Range<usize>>>());
+    assert_eq!(vec![0..1, 1..3, 1..4], contiguous_sums(&[5, 2, 3, 0], 5, 1).collect::<Vec<Range<usize>>>());
+    assert_eq!(vec![0..3], contiguous_sums(&[4, -2, 3, 1], 5, 3).collect::<Vec<Range<usize>>>());
   }
 }
\ No newline at end of file
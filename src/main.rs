@@ -1,5 +1,9 @@
 use std::env;
+use std::fs;
+use std::io::{self, Read};
 use std::process;
+use std::thread;
+use std::time::Duration;
 
 mod day1;
 mod day2;
@@ -20,6 +24,7 @@ mod day16;
 mod day17;
 mod day18;
 mod day19;
+mod number_theory;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -71,36 +76,275 @@ fn main() {
             println!("Day 7 possible gold containing bags {}", day7::count_bags_with_gold(&bag_rules));
             println!("Day 7 count required bags {}", day7::count_required_bags(&bag_rules));
         }
+        else if day == "day7breakdown" {
+            let bag_rules = day7::read_rules();
+            for (bag, count) in day7::bag_breakdown("shiny gold bag", &bag_rules) {
+                println!("Day 7 {} inside gold: {}", bag, count);
+            }
+        }
+        else if day == "day7dot" || day == "day7dot-in" || day == "day7dot-out" {
+            let bag_rules = day7::read_rules();
+            let scope = match day.as_str() {
+                "day7dot-in" => day7::DotScope::ContainedIn("shiny gold bag"),
+                "day7dot-out" => day7::DotScope::Containing("shiny gold bag"),
+                _ => day7::DotScope::All,
+            };
+            print!("{}", day7::rules_to_dot(&bag_rules, scope));
+        }
         else if day == "day8" {
             let boot_code = day8::read_boot_instructions();
             println!("Day 8 accumulator at infinite loop: {}", day8::accumulator_at_infinite_loop(&boot_code).1);
             println!("Day 8 program terminates with {}", day8::find_termination(&boot_code));
+            if let Some(repair) = day8::repair(&boot_code, &day8::InstructionSet::default()) {
+                println!("Day 8 repaired line {} from {} to {}", repair.line, repair.original, repair.replacement);
+            }
+            let cfg = day8::ControlFlowGraph::build(&boot_code, &day8::InstructionSet::default());
+            println!("Day 8 boot code has {} basic blocks, {} loops, and {} unreachable lines", cfg.blocks.len(), cfg.loops.len(), cfg.unreachable_lines().len());
+        }
+        else if day == "day8debug" {
+            day8::debug(&day8::read_boot_instructions());
+        }
+        else if day == "day8trace" || day == "day8trace-json" {
+            let boot_code = day8::read_boot_instructions();
+            let mut vm = day8::Vm::new(&boot_code);
+            vm.set_halt_policy(day8::HaltOnRepeat::default());
+            vm.enable_trace();
+            vm.run();
+            let trace = vm.take_trace();
+            if day == "day8trace" {
+                print!("{}", day8::trace_log(&trace));
+            } else {
+                println!("{}", day8::trace_json(&trace));
+            }
+        }
+        else if day == "day8cfg" {
+            let boot_code = day8::read_boot_instructions();
+            print!("{}", day8::ControlFlowGraph::build(&boot_code, &day8::InstructionSet::default()).to_dot(&boot_code));
+        }
+        else if day == "day8asm" || day == "day8run" {
+            let mut source = String::new();
+            io::stdin().read_to_string(&mut source).unwrap();
+            // anything after a --- line is input for the program
+            let (program, input) = source.split_once("\n---").unwrap_or((&source, ""));
+            let instruction_set = day8::InstructionSet::extended();
+            let code = match day8::assemble(program, &instruction_set) {
+                Ok(code) => code,
+                Err(e) => {
+                    println!("Day 8 could not assemble program: {}", e);
+                    continue;
+                }
+            };
+            if day == "day8asm" {
+                print!("{}", day8::disassemble(&code, &instruction_set));
+            } else {
+                let mut vm = day8::Vm::with_instruction_set(&code, &instruction_set);
+                vm.set_halt_policy(|_, steps| steps >= 1_000_000);
+                match input.split_whitespace().map(|value| value.parse().map_err(|_| value)).collect() {
+                    Ok(input) => vm.state_mut().input = input,
+                    Err(value) => {
+                        println!("Day 8 input '{}' is not a number", value);
+                        continue;
+                    }
+                }
+                let status = vm.run();
+                let mut registers = vm.state().registers.iter().collect::<Vec<(&char, &i32)>>();
+                registers.sort();
+                println!("Day 8 program stopped with {:?}", status);
+                println!("accumulator {} registers {:?} output {:?}", vm.accumulator(), registers, vm.state().output);
+            }
         }
         else if day == "day9" {
             let cypher = day9::read_cypher();
             let first_invalid = day9::find_first_not_sum(&cypher, 25);
             println!("Day 9 first not matching value {}", first_invalid);
-            println!("Day 9 find encryption weakness {}", day9::find_contiguous_sum(first_invalid, &cypher));
+            println!("Day 9 count of all not matching values {}", day9::find_all_not_sum(&cypher, 25).len());
+            println!("Day 9 find encryption weakness {}", day9::find_contiguous_sum(first_invalid, &cypher).unwrap());
+        }
+        else if day == "day9stream" {
+            // stop at the first bad line, since the numbers after it can't be checked against the right window
+            let mut error = None;
+            let numbers = day9::numbers_from_reader(io::stdin().lock())
+                .map_while(|number| number.map_err(|e| error = Some(e)).ok());
+            for event in day9::validate_stream(numbers, 25) {
+                if let day9::XmasEvent::Invalid { index, value } = event {
+                    println!("Day 9 invalid number {} at index {}", value, index);
+                }
+            }
+            if let Some(e) = error {
+                println!("Day 9 stopped reading: {}", e);
+            }
         }
         else if day == "day10" {
             let adapters = day10::read_adapters();
-            println!("Day 10 all adapter jolt diff {}", day10::jolt_diff_using_all_adapters(&adapters));
-            println!("Day 10 all combos {}", day10::total_configurations(&adapters));
+            match day10::jolt_diff_using_all_adapters(&adapters) {
+                Ok(jolt_diff) => println!("Day 10 all adapter jolt diff {}", jolt_diff),
+                Err(e) => println!("Day 10 adapters can't all be used: {}", e),
+            }
+            match day10::total_configurations(&adapters) {
+                Some(combos) => println!("Day 10 all combos {}", combos),
+                None => println!("Day 10 all combos {}", day10::count_arrangements(&adapters, &[1, 2, 3])),
+            }
+        }
+        else if day == "day10chains" {
+            let adapters = day10::read_adapters();
+            let shortest = day10::shortest_chain(&adapters, &[1, 2, 3]).unwrap();
+            let longest = day10::longest_chain(&adapters, &[1, 2, 3]).unwrap();
+            println!("Day 10 shortest chain uses {} adapters with jolt differences {:?}", shortest.len() - 2, day10::jolt_histogram(&shortest));
+            println!("Day 10 longest chain uses {} adapters with jolt differences {:?}", longest.len() - 2, day10::jolt_histogram(&longest));
+            for chain in day10::arrangements(&adapters, &[1, 2, 3]).take(3) {
+                println!("Day 10 arrangement {:?}", chain.iter().map(|&position| adapters[position]).collect::<Vec<i64>>());
+            }
         }
         else if day == "day11" {
             let seats = day11::parse_seating(&day11::read_input());
             println!("Day 11 total occupied when stable {}", day11::count_stable_occupied(&seats));
             println!("Day 11 total occupied when stable {}", day11::count_stable_los(&seats));
         }
+        else if day == "day11policies" {
+            let seats = day11::parse_seating(&day11::read_input());
+            let radius = day11::SeatingPolicy {
+                neighborhood: day11::Neighborhood::Radius(2),
+                rules: day11::SeatRules { sit_at_most: 0, leave_at_least: 12 },
+            };
+            // like a theater, people only care about the seats next to them in the same row
+            let same_row = day11::SeatingPolicy {
+                neighborhood: day11::Neighborhood::Custom(|row, col, seats| {
+                    (col.saturating_sub(1)..=col + 1)
+                        .filter(|&c| c != col && c < seats[row].len())
+                        .map(|c| (row, c))
+                        .collect()
+                }),
+                rules: day11::SeatRules { sit_at_most: 0, leave_at_least: 2 },
+            };
+            // everyone gets up as soon as anyone is near, and sits down when two or fewer are
+            let restless = day11::SeatingPolicy {
+                neighborhood: day11::Neighborhood::Adjacent,
+                rules: day11::SeatRules { sit_at_most: 2, leave_at_least: 1 },
+            };
+            for (name, policy) in [("within 2 seats", radius), ("with row neighbors", same_row), ("restless", restless)] {
+                let graph = day11::SeatGraph::build(&seats, &policy.neighborhood);
+                let mut simulation = day11::SeatSimulation::new(&graph, policy.rules);
+                let outcome = simulation.run(day11::MAX_ROUNDS);
+                println!("Day 11 {}: {}, {} occupied", name, outcome, simulation.occupied());
+            }
+        }
+        else if day == "day11animate" || day == "day11frames" {
+            let seats = day11::parse_seating(&day11::read_input());
+            let (frames, outcome) = day11::frames(&seats, &day11::SeatingPolicy::adjacent(), day11::MAX_ROUNDS);
+            let directory = "day11_frames";
+            if day == "day11frames" {
+                fs::create_dir_all(directory).expect("couldn't create the frame directory");
+            }
+            for frame in &frames {
+                if day == "day11animate" {
+                    // clear the screen and go back to the top left before drawing the next round
+                    print!("\x1b[2J\x1b[H");
+                    println!("Round {}: {} occupied", frame.round, frame.occupied);
+                    println!("{}", day11::render_ascii(&frame.seats));
+                    thread::sleep(Duration::from_millis(100));
+                } else {
+                    let name = format!("{}/round_{:03}", directory, frame.round);
+                    fs::write(format!("{}.txt", name), day11::render_ascii(&frame.seats)).expect("couldn't write frame");
+                    fs::write(format!("{}.ppm", name), day11::render_ppm(&frame.seats, 4)).expect("couldn't write frame");
+                    println!("Round {}: {} occupied", frame.round, frame.occupied);
+                }
+            }
+            println!("Day 11 {}", outcome);
+        }
         else if day == "day12" {
             let instructions = day12::parse_instructions(&day12::read_input());
-            println!("Day12 Manhattan distance {}", day12::navigate_and_get_position(&instructions));
-            println!("Day12 Navigate using waypoint {}", day12::naviage_using_waypoint(&instructions));
+            match day12::navigate_and_get_position(&instructions) {
+                Ok(distance) => println!("Day12 Manhattan distance {}", distance),
+                Err(e) => println!("Day12 couldn't navigate: {}", e),
+            }
+            match day12::naviage_using_waypoint(&instructions) {
+                Ok(distance) => println!("Day12 Navigate using waypoint {}", distance),
+                Err(e) => println!("Day12 couldn't navigate using waypoint: {}", e),
+            }
+        }
+        else if day.starts_with("day12-") || day == "day12models" {
+            let instructions = day12::parse_instructions(&day12::read_input());
+            let names = match day.strip_prefix("day12-") {
+                Some(name) => vec![name],
+                None => day12::MODEL_NAMES.to_vec(),
+            };
+            for name in names {
+                match day12::model_by_name(name) {
+                    Some(mut ship) => match day12::navigate(ship.as_mut(), &instructions) {
+                        Ok(distance) => println!("Day12 {} Manhattan distance {}", name, distance),
+                        Err(e) => println!("Day12 {} couldn't navigate: {}", name, e),
+                    },
+                    None => println!("Day12 unknown navigation model {}, try one of {}", name, day12::MODEL_NAMES.join(", ")),
+                }
+            }
+        }
+        else if day == "day12track" {
+            let instructions = day12::parse_instructions(&day12::read_input());
+            for (name, track) in [("ship", day12::record_track(&instructions)), ("waypoint ship", day12::record_waypoint_track(&instructions))] {
+                match track {
+                    Ok(track) => {
+                        let (south_west, north_east) = track.bounding_box();
+                        println!("Day12 {} travelled {} within east {}..{}, north {}..{}",
+                            name, track.distance_travelled(), south_west.east, north_east.east, south_west.north, north_east.north);
+                    }
+                    Err(e) => println!("Day12 couldn't navigate the {}: {}", name, e),
+                }
+            }
+        }
+        else if day == "day12simplify" {
+            let instructions = day12::parse_instructions(&day12::read_input());
+            for (name, simplified) in [("ship", day12::simplify(&instructions)), ("waypoint ship", day12::simplify_waypoint(&instructions))] {
+                match simplified {
+                    Ok(simplified) => println!("Day12 {} {} instructions simplify to {}",
+                        name, instructions.len(), simplified.iter().map(|i| i.to_string()).collect::<Vec<String>>().join(" ")),
+                    Err(e) => println!("Day12 couldn't navigate the {}: {}", name, e),
+                }
+            }
+        }
+        else if day == "day12svg" || day == "day12geojson" {
+            match day12::record_waypoint_track(&day12::parse_instructions(&day12::read_input())) {
+                Ok(track) if day == "day12svg" => print!("{}", track.to_svg()),
+                Ok(track) => println!("{}", track.to_geojson()),
+                Err(e) => eprintln!("Day12 couldn't navigate: {}", e),
+            }
         }
         else if day == "day13" {
             let bus_times = day13::parse_input_start_time(&day13::read_input());
-            println!("Day13 bus id times time to wait {}", day13::earliest_bus(bus_times.0, &bus_times.1));
-            println!("Day13 first matching timestamp {}", day13::find_first_contiguous_time(&day13::parse_input_with_offsets(&day13::read_input())));
+            if let Some(earliest) = day13::earliest_bus(bus_times.0, &bus_times.1) {
+                println!("Day13 bus id times time to wait {}", earliest);
+            }
+            match day13::find_first_contiguous_time(&day13::parse_input_with_offsets(&day13::read_input())) {
+                Some(timestamp) => println!("Day13 first matching timestamp {}", timestamp),
+                None => println!("Day13 no timestamp matches every bus"),
+            }
+        }
+        else if day == "day13solve" {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input).expect("couldn't read the schedule");
+            match day13::find_first_contiguous_time(&day13::parse_input_with_offsets(input.trim())) {
+                Some(timestamp) => println!("Day13 first matching timestamp {}", timestamp),
+                None => println!("Day13 no timestamp matches every bus"),
+            }
+        }
+        else if day == "day13timetable" {
+            let (start_time, _) = day13::parse_input_start_time(&day13::read_input());
+            let start_time = start_time as i64;
+            let schedule = day13::Schedule::new(day13::parse_input_with_offsets(&day13::read_input()));
+            for departure in schedule.next_departures(start_time) {
+                println!("Day13 bus {} next leaves at {}, {} minutes from now", departure.bus, departure.time, departure.time - start_time);
+            }
+            let timetable = schedule.timetable(start_time..start_time + 30)
+                .iter()
+                .map(|departure| format!("{}@{}", departure.bus, departure.time))
+                .collect::<Vec<String>>();
+            println!("Day13 departures in the next 30 minutes: {}", timetable.join(", "));
+            let most_frequent = schedule.next_departures(0).iter().take(3).map(|departure| departure.bus).collect::<Vec<i64>>();
+            if let Some(time) = schedule.depart_together(&most_frequent, start_time) {
+                println!("Day13 buses {:?} next leave together at {}", most_frequent, time);
+            }
+            if let Some(time) = schedule.first_offset_time_after(1_000_000_000_000_000) {
+                println!("Day13 first matching timestamp after 10^15 {}", time);
+            }
         }
         else if day == "day14" {
             let mask_instructions = day14::parse_input(&day14::read_input());
@@ -0,0 +1,196 @@
+/*
+  Number theory helpers shared between days.
+
+  Anything that can overflow or has no answer returns an Option rather than a wrong number.
+  The `_big` versions work on big integers for when an i128 isn't enough.
+*/
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed, Zero};
+
+/// Greatest common divisor, always zero or positive
+pub fn gcd(a: i128, b: i128) -> i128 {
+  extended_gcd(a, b).0.abs()
+}
+
+/// Least common multiple, None if it doesn't fit in an i128
+pub fn lcm(a: i128, b: i128) -> Option<i128> {
+  if a == 0 || b == 0 {
+    return Some(0);
+  }
+  (a / gcd(a, b)).checked_mul(b).map(|multiple| multiple.abs())
+}
+
+/// Returns (g, x, y) where a * x + b * y = g, and g divides both a and b.
+/// x and y are the Bézout coefficients.
+// see: https://en.wikipedia.org/wiki/Extended_Euclidean_algorithm#Pseudocode
+pub fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+  let (mut old_r, mut r) = (a, b);
+  let (mut old_s, mut s) = (1, 0);
+  let (mut old_t, mut t) = (0, 1);
+
+  // the coefficients never get bigger than a / gcd and b / gcd, so this can't overflow
+  while r != 0 {
+    let quotient = old_r / r;
+    (old_r, r) = (r, old_r - quotient * r);
+    (old_s, s) = (s, old_s - quotient * s);
+    (old_t, t) = (t, old_t - quotient * t);
+  }
+  (old_r, old_s, old_t)
+}
+
+/// The x in 0..modulus where a * x = 1 (mod modulus), None if a and modulus share a factor
+pub fn mod_inverse(a: i128, modulus: i128) -> Option<i128> {
+  if modulus <= 0 {
+    return None;
+  }
+  let (g, x, _) = extended_gcd(a.rem_euclid(modulus), modulus);
+  if g.abs() != 1 {
+    return None;
+  }
+  Some((x * g).rem_euclid(modulus))
+}
+
+/// Chinese Remainder Theorem for any moduli, not just coprime ones.
+/// Given (residue, modulus) pairs, finds the x with x = residue (mod modulus) for all of them.
+/// Returns (x, m) where x is the smallest such value that isn't negative, and every solution is x plus a multiple of m.
+/// None if the congruences contradict each other, a modulus isn't positive, or the numbers get too big for an i128.
+pub fn crt(congruences: &[(i128, i128)]) -> Option<(i128, i128)> {
+  congruences.iter()
+    .try_fold((0, 1), |(x, m), &(residue, modulus)| combine(x, m, residue, modulus))
+}
+
+// x = a1 (mod n1) and x = a2 (mod n2) can only both hold if they agree modulo g = gcd(n1, n2).
+// Then x = a1 + n1 * k, where n1 * k = a2 - a1 (mod n2), so k = (a2 - a1) / g * inverse(n1 / g) (mod n2 / g)
+fn combine(a1: i128, n1: i128, a2: i128, n2: i128) -> Option<(i128, i128)> {
+  if n1 <= 0 || n2 <= 0 {
+    return None;
+  }
+  let g = gcd(n1, n2);
+  let difference = a2.checked_sub(a1)?;
+  if difference % g != 0 {
+    return None;
+  }
+  let step = n2 / g;
+  let k = (difference / g).rem_euclid(step).checked_mul(mod_inverse(n1 / g, step)?)?.rem_euclid(step);
+  let modulus = lcm(n1, n2)?;
+  let x = n1.checked_mul(k)?.checked_add(a1.rem_euclid(n1))?.rem_euclid(modulus);
+  Some((x, modulus))
+}
+
+/// `crt` for big integers, None only if the congruences contradict each other or a modulus isn't positive
+pub fn crt_big(congruences: &[(BigInt, BigInt)]) -> Option<(BigInt, BigInt)> {
+  congruences.iter()
+    .try_fold((BigInt::zero(), BigInt::one()), |(x, m), (residue, modulus)| combine_big(&x, &m, residue, modulus))
+}
+
+fn combine_big(a1: &BigInt, n1: &BigInt, a2: &BigInt, n2: &BigInt) -> Option<(BigInt, BigInt)> {
+  if !n1.is_positive() || !n2.is_positive() {
+    return None;
+  }
+  let shared = gcd_big(n1, n2);
+  let difference = a2 - a1;
+  if !(&difference % &shared).is_zero() {
+    return None;
+  }
+  let step = n2 / &shared;
+  // n1 / shared and step have no factors in common, so this is always an inverse
+  let (_, inverse) = extended_gcd_big(&(n1 / &shared), &step);
+  let k = rem_euclid_big(&(&difference / &shared * inverse), &step);
+  let modulus = n1 / &shared * n2;
+  let x = rem_euclid_big(&(n1 * k + rem_euclid_big(a1, n1)), &modulus);
+  Some((x, modulus))
+}
+
+fn gcd_big(a: &BigInt, b: &BigInt) -> BigInt {
+  extended_gcd_big(a, b).0.abs()
+}
+
+/// Returns (g, x) where a * x = g (mod b)
+fn extended_gcd_big(a: &BigInt, b: &BigInt) -> (BigInt, BigInt) {
+  let mut s = BigInt::zero();
+  let mut old_s = BigInt::one();
+  let mut r = b.clone();
+  let mut old_r = a.clone();
+
+  while !r.is_zero() {
+    let quotient = &old_r / &r;
+    let new_r = &old_r - &quotient * &r;
+    let new_s = &old_s - &quotient * &s;
+    old_r = std::mem::replace(&mut r, new_r);
+    old_s = std::mem::replace(&mut s, new_s);
+  }
+  (old_r, old_s)
+}
+
+fn rem_euclid_big(a: &BigInt, modulus: &BigInt) -> BigInt {
+  let remainder = a % modulus;
+  if remainder.is_negative() { remainder + modulus } else { remainder }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_gcd() {
+    let bezout = |a, b| {
+      let (_, x, y) = extended_gcd(a, b);
+      (x, y)
+    };
+    assert_eq!((-9,2), bezout(15, 69));
+    assert_eq!((2,-1), bezout(7, 13));
+    assert_eq!((1,19), bezout(77, -4));
+    assert_eq!((1,-3), bezout(-14, -4));
+    assert_eq!((-3,1), bezout(-4, -14));
+    assert_eq!((24,-37), bezout(91, 59));
+
+    assert_eq!(3, gcd(15, 69));
+    assert_eq!(2, gcd(-14, -4));
+    assert_eq!(5, gcd(0, 5));
+    assert_eq!(Some(345), lcm(15, 69));
+    assert_eq!(Some(28), lcm(-14, 4));
+    assert_eq!(None, lcm(i128::MAX, 2));
+  }
+
+  #[test]
+  fn test_mod_inverse() {
+    assert_eq!(Some(2), mod_inverse(7, 13));
+    assert_eq!(Some(11), mod_inverse(-7, 13));
+    assert_eq!(None, mod_inverse(6, 9));
+    assert_eq!(None, mod_inverse(3, 0));
+  }
+
+  #[test]
+  fn test_crt() {
+    assert_eq!(Some((23, 105)), crt(&[(2, 3), (3, 5), (2, 7)]));
+    // moduli that share a factor but agree
+    assert_eq!(Some((4, 12)), crt(&[(0, 4), (4, 6)]));
+    assert_eq!(Some((10, 12)), crt(&[(-2, 4), (-2, 6)]));
+    // an even number can't be odd
+    assert_eq!(None, crt(&[(0, 4), (5, 6)]));
+    assert_eq!(None, crt(&[(1, 0)]));
+    assert_eq!(Some((0, 1)), crt(&[]));
+    assert_eq!(None, crt(&[(0, i128::MAX), (1, i128::MAX - 1)]));
+  }
+
+  #[test]
+  fn test_crt_big() {
+    let big = |congruences: &[(i128, i128)]| {
+      let congruences = congruences.iter()
+        .map(|&(residue, modulus)| (BigInt::from(residue), BigInt::from(modulus)))
+        .collect::<Vec<(BigInt, BigInt)>>();
+      crt_big(&congruences).map(|(x, m)| (x.to_string(), m.to_string()))
+    };
+    assert_eq!(Some(("23".to_string(), "105".to_string())), big(&[(2, 3), (3, 5), (2, 7)]));
+    assert_eq!(Some(("10".to_string(), "12".to_string())), big(&[(-2, 4), (-2, 6)]));
+    assert_eq!(None, big(&[(0, 4), (5, 6)]));
+    assert_eq!(None, big(&[(1, 0)]));
+
+    // too big for crt
+    let (x, m) = crt_big(&[(BigInt::zero(), BigInt::from(i128::MAX)), (BigInt::one(), BigInt::from(i128::MAX - 1))]).unwrap();
+    assert_eq!(BigInt::from(i128::MAX) * BigInt::from(i128::MAX - 1), m);
+    assert!((&x % BigInt::from(i128::MAX)).is_zero());
+    assert!((&x % BigInt::from(i128::MAX - 1)).is_one());
+  }
+}